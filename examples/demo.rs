@@ -6,7 +6,7 @@
 #![allow(clippy::multiple_crate_versions)]
 
 use procedural_lightning::{
-    spawn_procedural_lightning, LightningConfig, LightningTree,
+    spawn_procedural_lightning, DisplacementMode, LightningConfig, LightningTree,
     ProceduralLightning, ProceduralLightningPlugin,
 };
 use bevy::prelude::*;
@@ -50,6 +50,7 @@ struct DemoState {
     gamma: f32,
     max_depth: u32,
     max_branch_depth: u32,
+    volumetric: bool,
 
     // Visual settings
     color: [f32; 3],
@@ -102,6 +103,7 @@ impl Default for DemoState {
             gamma: 0.3,
             max_depth: 8,
             max_branch_depth: 3,
+            volumetric: false,
             color: [0.3, 0.7, 1.0], // Electric blue
             lifetime: 0.5,
             auto_spawn: false,
@@ -274,6 +276,9 @@ fn demo_ui(mut contexts: EguiContexts, mut demo_state: ResMut<DemoState>) {
             ui.add(egui::Slider::new(&mut demo_state.max_branch_depth, 0..=6));
             ui.label("Maximum nested branch levels");
 
+            ui.checkbox(&mut demo_state.volumetric, "3D Displacement");
+            ui.label("Offset around the segment axis instead of the ground plane");
+
             ui.separator();
 
             // Visual settings
@@ -390,6 +395,14 @@ fn update_config_preview(mut demo_state: ResMut<DemoState>) {
         gamma: demo_state.gamma,
         max_depth: demo_state.max_depth,
         max_branch_depth: demo_state.max_branch_depth,
+        displacement_mode: if demo_state.volumetric {
+            DisplacementMode::Volumetric
+        } else {
+            DisplacementMode::Planar
+        },
+        // Keep the deepest slider settings from stalling a frame
+        max_nodes: Some(50_000),
+        ..default()
     };
 
     let tree = LightningTree::generate(
@@ -455,6 +468,14 @@ fn spawn_lightning_on_click(
         gamma: demo_state.gamma,
         max_depth: demo_state.max_depth,
         max_branch_depth: demo_state.max_branch_depth,
+        displacement_mode: if demo_state.volumetric {
+            DisplacementMode::Volumetric
+        } else {
+            DisplacementMode::Planar
+        },
+        // Keep the deepest slider settings from stalling a frame
+        max_nodes: Some(50_000),
+        ..default()
     };
 
     let [r, g, b] = demo_state.color;
@@ -11,20 +11,55 @@
 //! 3. Spawning branches with decreasing probability
 //! 4. Rendering as connected line segments or particle chain
 
+use std::cmp::Reverse;
+use std::collections::{BinaryHeap, HashMap};
+use std::hash::{Hash, Hasher};
+use std::marker::PhantomData;
+
+use bevy::ecs::query::QueryFilter;
 use bevy::prelude::*;
 use bevy_hanabi::prelude::*;
-use rand::Rng;
+use rand::{Rng, RngCore};
 use rand_chacha::{rand_core::SeedableRng, ChaCha8Rng};
 
 /// A node in the lightning tree structure
 #[derive(Debug, Clone)]
 pub struct LightningNode {
-    /// Position in 3D space (game coordinates, XZ plane)
+    /// Position in 3D space (game coordinates)
     pub position: Vec3,
     /// Branch depth (0 = main bolt, higher = sub-branches)
     pub branch_depth: u32,
     /// Energy density at this node (0.0 = end, 1.0 = full power)
     pub energy: f32,
+    /// Index of the node this one grows from (`None` for the root)
+    pub parent: Option<usize>,
+    /// Indices of nodes growing from this one, the branch continuation first
+    pub children: Vec<usize>,
+    /// Branch this node belongs to (0 = main channel)
+    pub branch_id: usize,
+    /// Seconds after the bolt spawns at which the leader reaches this node
+    pub arrival_time: f32,
+    /// Subdivision level that created this node (0 = an original endpoint)
+    ///
+    /// Cutting the tree at a level keeps its silhouette with fewer segments,
+    /// see [`LightningTree::at_detail_level`].
+    pub level: u32,
+}
+
+impl LightningNode {
+    /// Create a node without topology; [`LightningTree::new`] fills it in
+    pub fn new(position: Vec3, branch_depth: u32, energy: f32) -> Self {
+        Self {
+            position,
+            branch_depth,
+            energy,
+            parent: None,
+            children: Vec::new(),
+            branch_id: 0,
+            arrival_time: 0.0,
+            level: 0,
+        }
+    }
 }
 
 /// Configuration for lightning generation algorithm
@@ -42,9 +77,301 @@ pub struct LightningConfig {
     /// Typical: 0.2 - 0.6
     pub gamma: f32,
     /// Maximum recursion depth for subdivision
+    /// (acts as a safety cap when `target_segment_length` is set)
     pub max_depth: u32,
     /// Maximum branch depth (prevent infinite branching)
     pub max_branch_depth: u32,
+    /// How midpoint and branch offsets are oriented around each segment
+    pub displacement_mode: DisplacementMode,
+    /// Plane used by [`DisplacementMode::Planar`] (XZ for top-down/3D, XY for 2D/side-view)
+    pub displacement_plane: DisplacementPlane,
+    /// Stop subdividing once segments are shorter than this world-space length,
+    /// so detail stays consistent across bolt sizes. `None` = always subdivide to `max_depth`
+    pub target_segment_length: Option<f32>,
+    /// Length and spread of spawned branches
+    pub branch: BranchConfig,
+    /// Energy assigned to the end node and to branches
+    pub energy: EnergyConfig,
+    /// Stepped-leader timing used to animate the bolt's growth
+    pub growth: GrowthConfig,
+    /// Separate seed for displacement offsets and branch directions. When set, `seed`
+    /// alone decides the topology, so trees that only differ in this seed can be
+    /// blended with [`LightningTree::lerp`]. `None` = draw everything from `seed`
+    pub displacement_seed: Option<u64>,
+    /// How random numbers are assigned to subdivisions
+    pub seed_mode: SeedMode,
+    /// Roughness (Hurst exponent) of the displacement across subdivision levels.
+    /// 1.0 keeps the same jaggedness at every level, lower values get rougher in the
+    /// fine detail (0.5 = Brownian), higher values smoother. Typical: 0.3 - 1.5
+    pub hurst_exponent: f32,
+    /// Distribution of the random midpoint displacement
+    pub displacement_distribution: DisplacementDistribution,
+    /// Take midpoint offsets from a continuous noise field instead of the RNG, so
+    /// nearby bolts share their large-scale shape. `None` = independent random offsets
+    pub noise: Option<NoiseDisplacement>,
+    /// Hard cap on the number of nodes. A bolt that fits is identical to an uncapped
+    /// one; otherwise it is regenerated main channel first and the remaining segments
+    /// are left unsubdivided (see [`LightningTree::truncated`]). The starting nodes
+    /// (root, endpoints and fork points) are always kept, so smaller caps act as that count
+    pub max_nodes: Option<usize>,
+    /// Hard cap on the number of segments, applied like `max_nodes` (at least one
+    /// segment per starting channel is always kept)
+    pub max_segments: Option<usize>,
+    /// Removal of tiny, faint branches after generation
+    pub prune: PruneConfig,
+}
+
+/// Thresholds below which branches are removed (see [`LightningTree::prune`])
+///
+/// Both default to 0.0, which keeps every branch.
+#[derive(Debug, Clone, Default)]
+pub struct PruneConfig {
+    /// Branches shorter than this (including their sub-branches) are removed
+    pub min_length: f32,
+    /// Branches whose tip energy is below this are removed
+    pub min_tip_energy: f32,
+}
+
+impl PruneConfig {
+    fn key(&self) -> [u32; 2] {
+        [self.min_length.to_bits(), self.min_tip_energy.to_bits()]
+    }
+}
+
+/// Continuous fBm noise field used for midpoint displacement
+///
+/// The field is sampled at each undisplaced midpoint's world position, so bolts in the
+/// same area bend the same way. Move `offset` over time to make the shape flow.
+#[derive(Debug, Clone)]
+pub struct NoiseDisplacement {
+    /// Frequency of the base octave in cycles per world unit (1 / feature size)
+    pub frequency: f32,
+    /// Number of noise octaves summed together
+    pub octaves: u32,
+    /// Frequency multiplier between octaves
+    pub lacunarity: f32,
+    /// Amplitude multiplier between octaves
+    pub gain: f32,
+    /// Offset into the noise field (in noise space)
+    pub offset: Vec3,
+}
+
+impl Default for NoiseDisplacement {
+    fn default() -> Self {
+        Self {
+            frequency: 0.02,
+            octaves: 4,
+            lacunarity: 2.0,
+            gain: 0.5,
+            offset: Vec3::ZERO,
+        }
+    }
+}
+
+impl NoiseDisplacement {
+    /// Displacement factor at `position`, with about the same spread as the random offsets
+    pub fn sample(&self, position: Vec3) -> f32 {
+        self.field(position, 0.0)
+    }
+
+    /// fBm at `position`, shifted by `shift` in noise space for independent channels
+    fn field(&self, position: Vec3, shift: f32) -> f32 {
+        let base = position * self.frequency + self.offset + Vec3::splat(shift);
+        let mut value = 0.0;
+        let mut total = 0.0;
+        let mut amplitude = 1.0;
+        let mut frequency = 1.0;
+        for octave in 0..self.octaves.max(1) {
+            // Irrational-ish shift per octave so lattice points never line up
+            let shift = NOISE_OCTAVE_SHIFT * (octave + 1) as f32;
+            value += amplitude * perlin_noise(base * frequency + shift);
+            total += amplitude;
+            amplitude *= self.gain;
+            frequency *= self.lacunarity;
+        }
+        value / total * NOISE_DISPLACEMENT_SCALE
+    }
+
+    fn key(&self) -> ([u32; 3], u32, [u32; 3]) {
+        (
+            [
+                self.frequency.to_bits(),
+                self.lacunarity.to_bits(),
+                self.gain.to_bits(),
+            ],
+            self.octaves,
+            self.offset.to_array().map(f32::to_bits),
+        )
+    }
+}
+
+/// Distribution that midpoint displacements are drawn from
+///
+/// All three have roughly the same typical spread, so `beta` means the same for each.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+pub enum DisplacementDistribution {
+    /// Uniform in `-1.0..1.0`
+    #[default]
+    Uniform,
+    /// Normal distribution, as in a Brownian bridge; smoother with occasional kinks
+    Gaussian,
+    /// Heavy-tailed Cauchy distribution (clamped), for sudden sharp zig-zags
+    Cauchy,
+}
+
+/// How subdivision draws its random numbers
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+pub enum SeedMode {
+    /// One RNG stream for the whole bolt; any parameter change reshuffles everything
+    #[default]
+    Stream,
+    /// Every subdivision gets its own RNG derived from the seed and its path in the
+    /// tree, so coarse shape survives edits to `max_depth`, `gamma` and friends
+    Hierarchical,
+}
+
+/// Shape parameters for spawned branches
+#[derive(Debug, Clone)]
+pub struct BranchConfig {
+    /// Branch length as a fraction of the parent segment length (before alpha decay)
+    pub length_factor: f32,
+    /// Sideways component of the branch direction is sampled in `-spread..spread`
+    pub perpendicular_spread: f32,
+    /// Minimum forward (toward target) component of the branch direction
+    pub forward_min: f32,
+    /// Maximum forward (toward target) component of the branch direction
+    pub forward_max: f32,
+    /// Fraction of the way toward the targets' centroid where multi-target strikes
+    /// ([`LightningTree::generate_forked`]) split into separate channels
+    pub fork_fraction: f32,
+}
+
+impl Default for BranchConfig {
+    fn default() -> Self {
+        Self {
+            length_factor: 0.5,
+            perpendicular_spread: 0.6,
+            forward_min: 0.3,
+            forward_max: 0.8,
+            fork_fraction: 0.5,
+        }
+    }
+}
+
+/// Energy distribution for generated nodes
+#[derive(Debug, Clone)]
+pub struct EnergyConfig {
+    /// Energy of the end node (the root always has 1.0)
+    pub end_energy: f32,
+    /// Multiplier applied to the energy where a branch forks off
+    pub branch_factor: f32,
+    /// How node energy is derived once the tree is built
+    pub model: EnergyModel,
+}
+
+impl Default for EnergyConfig {
+    fn default() -> Self {
+        Self {
+            end_energy: 0.8,
+            branch_factor: 0.5,
+            model: EnergyModel::Interpolated,
+        }
+    }
+}
+
+/// How node energy is derived; every model keeps energy in `0.0..=1.0` with 1.0 at the root
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+pub enum EnergyModel {
+    /// Midpoints average their endpoints and branches start at `branch_factor` times
+    /// the energy where they fork (the generator's own energies)
+    #[default]
+    Interpolated,
+    /// Energy follows the current through each node: the number of tips downstream of
+    /// it, with every strike point weighing as much as all branch tips together, so the
+    /// main channel always carries the most
+    DownstreamTips,
+    /// Energy decays with path length from the root, reaching `end_energy` at the
+    /// primary strike point, and is scaled by `branch_factor` per branch level
+    PathDecay,
+}
+
+/// Stepped-leader timing for [`LightningNode::arrival_time`]
+///
+/// The leader travels along every channel at `leader_speed`, so nodes further from
+/// the root (by path distance) light up later. Each branch gets its own speed and the
+/// leader may stall along the way, so branches race ahead of each other. Stalls depend
+/// on the distance travelled, not the node count, so the timing is the same at any
+/// subdivision depth. Travel is measured along the jagged path, which keeps getting
+/// longer with depth when `hurst_exponent` is 1.0 or below.
+#[derive(Debug, Clone)]
+pub struct GrowthConfig {
+    /// Leader speed in world units per second (0.0 = the whole bolt appears at once)
+    /// Typical: 1 - 5 bolt lengths per second
+    pub leader_speed: f32,
+    /// Each branch's speed is scaled by a random factor in `1 - variation..1 + variation`
+    pub speed_variation: f32,
+    /// Chance per world unit travelled that the leader pauses before continuing
+    /// (about the expected number of pauses per unit)
+    pub stall_chance: f32,
+    /// Longest pause in seconds; pauses are sampled in `0..stall_duration`
+    pub stall_duration: f32,
+}
+
+impl Default for GrowthConfig {
+    fn default() -> Self {
+        Self {
+            leader_speed: 0.0,
+            speed_variation: 0.3,
+            stall_chance: 0.02,
+            stall_duration: 0.05,
+        }
+    }
+}
+
+/// Orientation of the perpendicular offsets applied during subdivision
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+pub enum DisplacementMode {
+    /// Offsets stay in the configured [`DisplacementPlane`], producing flat bolts
+    #[default]
+    Planar,
+    /// Offsets point in a random direction around the segment axis,
+    /// so bolts look three-dimensional from any camera angle
+    Volumetric,
+}
+
+/// Plane that planar displacement is confined to
+#[derive(Debug, Clone, Copy, Default)]
+pub enum DisplacementPlane {
+    /// Ground plane with Y up (top-down and 3D games)
+    #[default]
+    XZ,
+    /// Screen plane with Z as the normal (2D and side-view games)
+    XY,
+    /// Vertical plane with X as the normal
+    YZ,
+    /// Arbitrary plane given by its normal
+    Custom(Vec3),
+}
+
+impl DisplacementPlane {
+    /// Unit normal of the plane
+    pub fn normal(self) -> Vec3 {
+        match self {
+            Self::XZ => Vec3::Y,
+            Self::XY => Vec3::Z,
+            Self::YZ => Vec3::X,
+            Self::Custom(normal) => normal.normalize_or(Vec3::Y),
+        }
+    }
+
+    /// In-plane direction used when a segment runs along the normal
+    fn fallback_axis(self) -> Vec3 {
+        match self {
+            Self::XZ | Self::XY => Vec3::X,
+            Self::YZ => Vec3::Z,
+            Self::Custom(_) => self.normal().any_orthonormal_vector(),
+        }
+    }
 }
 
 impl Default for LightningConfig {
@@ -56,6 +383,171 @@ impl Default for LightningConfig {
             gamma: 0.3,
             max_depth: 8,
             max_branch_depth: 3,
+            displacement_mode: DisplacementMode::Planar,
+            displacement_plane: DisplacementPlane::XZ,
+            target_segment_length: None,
+            branch: BranchConfig::default(),
+            energy: EnergyConfig::default(),
+            growth: GrowthConfig::default(),
+            displacement_seed: None,
+            seed_mode: SeedMode::Stream,
+            hurst_exponent: 1.0,
+            displacement_distribution: DisplacementDistribution::Uniform,
+            noise: None,
+            max_nodes: None,
+            max_segments: None,
+            prune: PruneConfig::default(),
+        }
+    }
+}
+
+/// Implements `PartialEq`, `Eq` and `Hash` through the type's `key()`
+///
+/// Keys hold floats as their bit patterns, so configs can be used as map keys
+/// (see [`LightningCache`]).
+macro_rules! impl_eq_hash_by_key {
+    ($($ty:ty),* $(,)?) => {$(
+        impl PartialEq for $ty {
+            fn eq(&self, other: &Self) -> bool {
+                self.key() == other.key()
+            }
+        }
+
+        impl Eq for $ty {}
+
+        impl Hash for $ty {
+            fn hash<H: Hasher>(&self, state: &mut H) {
+                self.key().hash(state);
+            }
+        }
+    )*};
+}
+
+impl_eq_hash_by_key!(
+    LightningConfig,
+    BranchConfig,
+    EnergyConfig,
+    GrowthConfig,
+    DisplacementPlane,
+    NoiseDisplacement,
+    PruneConfig,
+);
+
+impl LightningConfig {
+    fn key(&self) -> impl Eq + Hash + '_ {
+        let shape = (
+            [
+                self.alpha.to_bits(),
+                self.beta.to_bits(),
+                self.gamma.to_bits(),
+                self.hurst_exponent.to_bits(),
+            ],
+            self.displacement_mode,
+            &self.displacement_plane,
+            self.displacement_distribution,
+            &self.noise,
+        );
+        let subdivision = (
+            self.max_depth,
+            self.max_branch_depth,
+            self.target_segment_length.map(f32::to_bits),
+            self.max_nodes,
+            self.max_segments,
+        );
+        let seeding = (self.seed, self.displacement_seed, self.seed_mode);
+        (
+            shape,
+            subdivision,
+            seeding,
+            &self.branch,
+            &self.energy,
+            &self.growth,
+            &self.prune,
+        )
+    }
+}
+
+impl BranchConfig {
+    fn key(&self) -> [u32; 5] {
+        [
+            self.length_factor.to_bits(),
+            self.perpendicular_spread.to_bits(),
+            self.forward_min.to_bits(),
+            self.forward_max.to_bits(),
+            self.fork_fraction.to_bits(),
+        ]
+    }
+}
+
+impl EnergyConfig {
+    fn key(&self) -> ([u32; 2], EnergyModel) {
+        (
+            [self.end_energy.to_bits(), self.branch_factor.to_bits()],
+            self.model,
+        )
+    }
+}
+
+impl GrowthConfig {
+    fn key(&self) -> [u32; 4] {
+        [
+            self.leader_speed.to_bits(),
+            self.speed_variation.to_bits(),
+            self.stall_chance.to_bits(),
+            self.stall_duration.to_bits(),
+        ]
+    }
+}
+
+impl DisplacementPlane {
+    fn key(&self) -> (u8, [u32; 3]) {
+        match self {
+            Self::XZ => (0, [0; 3]),
+            Self::XY => (1, [0; 3]),
+            Self::YZ => (2, [0; 3]),
+            Self::Custom(normal) => (3, normal.to_array().map(f32::to_bits)),
+        }
+    }
+}
+
+/// Configuration for the Dielectric Breakdown Model (DBM) generator
+///
+/// DBM grows the channel one grid cell at a time, favouring cells with a high
+/// electric potential. It is far slower than subdivision but produces much more
+/// realistic branching.
+#[derive(Debug, Clone)]
+pub struct DbmConfig {
+    /// Random seed for reproducible generation
+    pub seed: u64,
+    /// Eta: exponent applied to the potential when picking growth cells
+    /// Higher = straighter channel with fewer branches. Typical: 1.0 - 4.0
+    pub eta: f32,
+    /// Number of grid cells between start and end
+    /// Typical: 32 - 96 for planar grids, 16 - 32 for volumetric grids
+    pub resolution: u32,
+    /// Planar grids grow in a 2D slice, volumetric grids in a full 3D volume
+    pub grid: DisplacementMode,
+    /// Plane of the grid when `grid` is [`DisplacementMode::Planar`]
+    pub plane: DisplacementPlane,
+    /// Laplace relaxation sweeps after each growth step (higher = more accurate, slower)
+    pub relaxation_iterations: u32,
+    /// Energy assigned to the end node and to branches
+    pub energy: EnergyConfig,
+    /// Stepped-leader timing used to animate the bolt's growth
+    pub growth: GrowthConfig,
+}
+
+impl Default for DbmConfig {
+    fn default() -> Self {
+        Self {
+            seed: 0,
+            eta: 2.0,
+            resolution: 48,
+            grid: DisplacementMode::Planar,
+            plane: DisplacementPlane::XZ,
+            relaxation_iterations: 8,
+            energy: EnergyConfig::default(),
+            growth: GrowthConfig::default(),
         }
     }
 }
@@ -67,445 +559,4120 @@ pub struct LightningTree {
     pub root: Vec3,
     /// All nodes in the tree (main path + branches)
     pub nodes: Vec<LightningNode>,
-    /// Pairs of node indices representing line segments
+    /// Pairs of node indices representing line segments (parent, child)
     pub segments: Vec<(usize, usize)>,
+    /// Node indices of the main channel, from the root (node 0) to the end of the bolt
+    pub main_path: Vec<usize>,
+    /// Nodes where the bolt hits its targets; the first one ends the main path
+    pub strike_points: Vec<usize>,
+    /// Whether generation stopped early because `max_nodes` or `max_segments` was reached
+    pub truncated: bool,
 }
 
 impl LightningTree {
-    /// Generate a procedural lightning tree from start to end position
-    pub fn generate(start: Vec3, end: Vec3, config: &LightningConfig) -> Self {
-        let mut rng = ChaCha8Rng::seed_from_u64(config.seed);
-        let mut nodes = Vec::new();
-        let mut segments = Vec::new();
+    /// Assemble a tree from nodes and (parent, child) segments, building its topology
+    ///
+    /// Node 0 must be the root. Custom [`LightningGenerator`]s should build trees
+    /// through this so that parent/child links, branch ids and the main path are set.
+    /// The strike point is inferred from the main channel; use
+    /// [`LightningTree::with_strike_points`] when the targets are known.
+    pub fn new(root: Vec3, nodes: Vec<LightningNode>, segments: Vec<(usize, usize)>) -> Self {
+        Self::with_strike_points(root, nodes, segments, Vec::new())
+    }
 
-        // Start with the root segment
-        let start_node = LightningNode {
-            position: start,
-            branch_depth: 0,
-            energy: 1.0,
-        };
-        let end_node = LightningNode {
-            position: end,
-            branch_depth: 0,
-            energy: 0.8,
+    /// Assemble a tree whose main path ends at `strike_points[0]`
+    pub fn with_strike_points(
+        root: Vec3,
+        nodes: Vec<LightningNode>,
+        segments: Vec<(usize, usize)>,
+        strike_points: Vec<usize>,
+    ) -> Self {
+        let mut tree = Self {
+            root,
+            nodes,
+            segments,
+            main_path: Vec::new(),
+            strike_points,
+            truncated: false,
         };
+        tree.rebuild_topology();
+        tree
+    }
 
-        nodes.push(start_node.clone());
-        nodes.push(end_node.clone());
-
-        // Queue of segments to subdivide: (start_idx, end_idx, current_depth, branch_depth)
-        let mut to_subdivide = vec![(0, 1, 0, 0)];
-
-        while let Some((start_idx, end_idx, depth, branch_depth)) = to_subdivide.pop() {
-            if depth >= config.max_depth {
-                // Max depth reached, just connect as final segment
-                segments.push((start_idx, end_idx));
-                continue;
-            }
+    /// Recompute parent/child links, branch ids and the main path from `segments`
+    ///
+    /// The main path runs from the root to `strike_points[0]`. At every other node the
+    /// child with the lowest `branch_depth` (then the largest subtree) continues the
+    /// current branch, and every other child starts a new branch. If `strike_points`
+    /// is empty, the main path follows the root's continuation and its last node
+    /// becomes the only strike point.
+    pub fn rebuild_topology(&mut self) {
+        for node in &mut self.nodes {
+            node.parent = None;
+            node.children.clear();
+            node.branch_id = 0;
+        }
+        self.main_path.clear();
+        if self.nodes.is_empty() {
+            self.strike_points.clear();
+            return;
+        }
 
-            let start_pos = nodes[start_idx].position;
-            let end_pos = nodes[end_idx].position;
-            let start_energy = nodes[start_idx].energy;
-            let end_energy = nodes[end_idx].energy;
+        for &(parent, child) in &self.segments {
+            self.nodes[child].parent = Some(parent);
+            self.nodes[parent].children.push(child);
+        }
 
-            // Calculate midpoint with random perpendicular offset
-            let segment = end_pos - start_pos;
-            let length = segment.length();
-            let midpoint = (start_pos + end_pos) * 0.5;
+        let order = self.breadth_first_order();
 
-            // Random perpendicular vector in XZ plane (Y is up)
-            let perpendicular = if segment.x.abs() > 0.01 || segment.z.abs() > 0.01 {
-                Vec3::new(-segment.z, 0.0, segment.x).normalize()
-            } else {
-                // Segment is vertical, use X as perpendicular
-                Vec3::new(1.0, 0.0, 0.0)
-            };
+        let mut subtree_size = vec![1usize; self.nodes.len()];
+        for &idx in order.iter().rev() {
+            if let Some(parent) = self.nodes[idx].parent {
+                subtree_size[parent] += subtree_size[idx];
+            }
+        }
 
-            // Random displacement: beta controls magnitude, random value controls direction
-            let displacement = rng.gen_range(-1.0..1.0);
-            let offset = perpendicular * displacement * config.beta * length;
-            let displaced_midpoint = midpoint + offset;
+        // Nodes between the root and the primary strike point always continue the main channel
+        let mut on_main_path = vec![false; self.nodes.len()];
+        let mut cursor = self.strike_points.first().copied();
+        while let Some(idx) = cursor {
+            on_main_path[idx] = true;
+            cursor = self.nodes[idx].parent;
+        }
+        if !on_main_path[0] {
+            on_main_path.fill(false);
+        }
 
-            // Create middle node
-            let mid_node = LightningNode {
-                position: displaced_midpoint,
-                branch_depth,
-                energy: (start_energy + end_energy) * 0.5,
-            };
-            let mid_idx = nodes.len();
-            nodes.push(mid_node.clone());
-
-            // Queue subdivisions for both halves
-            to_subdivide.push((start_idx, mid_idx, depth + 1, branch_depth));
-            to_subdivide.push((mid_idx, end_idx, depth + 1, branch_depth));
-
-            // Branch generation with probability decay
-            if branch_depth < config.max_branch_depth {
-                let branch_prob =
-                    config.gamma * f32::exp(-config.alpha * (depth as f32 + branch_depth as f32));
-
-                if rng.gen::<f32>() < branch_prob {
-                    // Spawn a branch from the midpoint
-                    let branch_length =
-                        length * 0.5 * f32::exp(-config.alpha * branch_depth as f32);
-
-                    // Calculate overall direction to target (end point)
-                    let to_target = (end - displaced_midpoint).normalize();
-
-                    // Random branch direction: blend perpendicular offset with forward direction
-                    // This ensures branches generally move toward the target
-                    let perp_component = perpendicular * rng.gen_range(-0.6..0.6);
-                    let forward_component = to_target * rng.gen_range(0.3..0.8);
-                    let branch_dir = (perp_component + forward_component).normalize();
-
-                    let branch_end_pos = displaced_midpoint + branch_dir * branch_length;
-                    let branch_end_node = LightningNode {
-                        position: branch_end_pos,
-                        branch_depth: branch_depth + 1,
-                        energy: mid_node.energy * 0.5, // Branches have lower energy
-                    };
-                    let branch_end_idx = nodes.len();
-                    nodes.push(branch_end_node);
+        let mut next_branch_id = 1;
+        for &idx in &order {
+            let mut children = std::mem::take(&mut self.nodes[idx].children);
+            let continuation = children
+                .iter()
+                .enumerate()
+                .min_by_key(|&(_, &child)| {
+                    (
+                        !on_main_path[child],
+                        self.nodes[child].branch_depth,
+                        std::cmp::Reverse(subtree_size[child]),
+                    )
+                })
+                .map(|(position, _)| position);
+            if let Some(position) = continuation {
+                children.swap(0, position);
+            }
 
-                    // Queue branch for subdivision
-                    to_subdivide.push((mid_idx, branch_end_idx, depth + 1, branch_depth + 1));
-                }
+            for (position, &child) in children.iter().enumerate() {
+                self.nodes[child].branch_id = if position == 0 {
+                    self.nodes[idx].branch_id
+                } else {
+                    next_branch_id += 1;
+                    next_branch_id - 1
+                };
             }
+            self.nodes[idx].children = children;
         }
 
-        Self {
-            root: start,
-            nodes,
-            segments,
+        // Follow the main channel's continuation from the root
+        let mut current = 0;
+        self.main_path.push(current);
+        while let Some(&next) = self.nodes[current].children.first() {
+            if self.nodes[next].branch_id != 0 {
+                break;
+            }
+            self.main_path.push(next);
+            current = next;
+        }
+        if !on_main_path[0] {
+            self.strike_points = vec![current];
         }
     }
 
-    /// Get the total number of segments in the tree
-    pub fn segment_count(&self) -> usize {
-        self.segments.len()
+    /// Node indices reachable from the root, in breadth-first order
+    ///
+    /// Parents always come before their children.
+    fn breadth_first_order(&self) -> Vec<usize> {
+        if self.nodes.is_empty() {
+            return Vec::new();
+        }
+        let mut order = vec![0];
+        let mut cursor = 0;
+        while cursor < order.len() {
+            order.extend_from_slice(&self.nodes[order[cursor]].children);
+            cursor += 1;
+        }
+        order
     }
 
-    /// Get all line positions for rendering as a line strip or gizmos
-    pub fn get_line_positions(&self) -> Vec<Vec3> {
-        self.segments
+    /// Number of distinct branches, including the main channel
+    pub fn branch_count(&self) -> usize {
+        self.nodes
             .iter()
-            .flat_map(|(start_idx, end_idx)| {
-                [
-                    self.nodes[*start_idx].position,
-                    self.nodes[*end_idx].position,
-                ]
-            })
-            .collect()
+            .map(|node| node.branch_id + 1)
+            .max()
+            .unwrap_or(0)
     }
 
-    /// Sample positions along the lightning path for particle spawning
-    /// Returns positions evenly spaced along the lightning tree
-    pub fn sample_particle_positions(&self, particle_count: usize) -> Vec<Vec3> {
-        if self.segments.is_empty() {
+    /// Nodes of one branch in order from its first node to its tip
+    ///
+    /// The node the branch forks from belongs to the parent branch and is not included.
+    pub fn branch_nodes(&self, branch_id: usize) -> Vec<usize> {
+        let starts_branch = |idx: usize| {
+            let node = &self.nodes[idx];
+            match node.parent {
+                Some(parent) => self.nodes[parent].branch_id != branch_id,
+                None => idx == 0,
+            }
+        };
+        let Some(first) = (0..self.nodes.len())
+            .find(|&idx| self.nodes[idx].branch_id == branch_id && starts_branch(idx))
+        else {
             return Vec::new();
-        }
-
-        let mut positions = Vec::with_capacity(particle_count);
-        let segments_per_particle = self.segments.len().max(1) / particle_count.max(1);
+        };
 
-        for (i, (start_idx, end_idx)) in self.segments.iter().enumerate() {
-            if i % segments_per_particle == 0 && positions.len() < particle_count {
-                // Interpolate along the segment
-                let t = 0.5; // Use midpoint
-                let start_pos = self.nodes[*start_idx].position;
-                let end_pos = self.nodes[*end_idx].position;
-                positions.push(start_pos.lerp(end_pos, t));
+        let mut path = vec![first];
+        let mut current = first;
+        while let Some(&next) = self.nodes[current].children.first() {
+            if self.nodes[next].branch_id != branch_id {
+                break;
             }
+            path.push(next);
+            current = next;
         }
+        path
+    }
 
-        positions
+    /// Indices of all branch tips (nodes without children)
+    pub fn tips(&self) -> impl Iterator<Item = usize> + '_ {
+        self.nodes
+            .iter()
+            .enumerate()
+            .filter(|(idx, node)| node.children.is_empty() && (*idx == 0 || node.parent.is_some()))
+            .map(|(idx, _)| idx)
     }
 
-    /// Get energy-weighted positions for particle brightness
-    pub fn get_particle_data(&self, particle_count: usize) -> Vec<(Vec3, f32)> {
-        if self.segments.is_empty() {
-            return Vec::new();
+    /// Assign every node's `arrival_time` from its path distance to the root
+    ///
+    /// Uses its own RNG seeded from `seed`, so the timing never changes the bolt's
+    /// shape. With a `leader_speed` of 0.0 every node arrives at 0.0.
+    pub fn assign_arrival_times(&mut self, growth: &GrowthConfig, seed: u64) {
+        for node in &mut self.nodes {
+            node.arrival_time = 0.0;
+        }
+        if growth.leader_speed <= 0.0 || self.nodes.is_empty() {
+            return;
         }
 
-        let mut data = Vec::with_capacity(particle_count);
-        let segments_per_particle = self.segments.len().max(1) / particle_count.max(1);
+        let mut rng = ChaCha8Rng::seed_from_u64(seed ^ GROWTH_SEED_SALT);
+        let variation = growth.speed_variation.clamp(0.0, 0.95);
+        let branch_speed: Vec<f32> = (0..self.branch_count())
+            .map(|_| growth.leader_speed * sample_range(&mut rng, 1.0 - variation, 1.0 + variation))
+            .collect();
 
-        for (i, (start_idx, end_idx)) in self.segments.iter().enumerate() {
-            if i % segments_per_particle == 0 && data.len() < particle_count {
-                let start_node = &self.nodes[*start_idx];
-                let end_node = &self.nodes[*end_idx];
-                let t = 0.5;
-                let position = start_node.position.lerp(end_node.position, t);
-                let energy = start_node.energy * (1.0 - t) + end_node.energy * t;
-                data.push((position, energy));
-            }
+        // A node's parent always has its time assigned first
+        for idx in self.breadth_first_order() {
+            let Some(parent) = self.nodes[idx].parent else {
+                continue;
+            };
+            let distance = self.nodes[idx]
+                .position
+                .distance(self.nodes[parent].position);
+            // Pauses arrive at a fixed rate per world unit, however finely the path is cut
+            let stall_probability = 1.0 - (-growth.stall_chance.max(0.0) * distance).exp();
+            let stall = if rng.gen::<f32>() < stall_probability {
+                sample_range(&mut rng, 0.0, growth.stall_duration)
+            } else {
+                0.0
+            };
+            self.nodes[idx].arrival_time = self.nodes[parent].arrival_time
+                + distance / branch_speed[self.nodes[idx].branch_id]
+                + stall;
         }
-
-        data
     }
-}
 
-/// Component for a procedural lightning effect entity
-#[derive(Component)]
-pub struct ProceduralLightning {
-    /// The generated lightning tree
-    pub tree: LightningTree,
-    /// Animation timer for flickering/pulsing
-    pub animation_timer: Timer,
-    /// Lifetime timer for despawning
-    pub lifetime: Timer,
-    /// Base color for the lightning
-    pub color: Color,
-    /// Particle effect entities (core, glow, sparks, impact)
-    pub particle_entities: Vec<Entity>,
-    /// Whether to render debug gizmos
-    pub show_gizmos: bool,
-    /// Whether to enable flicker effect (on/off intervals)
-    pub enable_flicker: bool,
-}
+    /// Recompute node energies from the topology according to `energy.model`
+    ///
+    /// [`EnergyModel::Interpolated`] leaves the energies untouched.
+    pub fn apply_energy_model(&mut self, energy: &EnergyConfig) {
+        if energy.model == EnergyModel::Interpolated || self.nodes.is_empty() {
+            return;
+        }
 
-impl ProceduralLightning {
-    /// Create a new procedural lightning effect
-    pub fn new(
-        start: Vec3,
-        end: Vec3,
-        config: &LightningConfig,
-        lifetime_secs: f32,
-        color: Color,
-    ) -> Self {
-        let tree = LightningTree::generate(start, end, config);
+        let order = self.breadth_first_order();
 
-        Self {
-            tree,
-            animation_timer: Timer::from_seconds(0.05, TimerMode::Repeating),
-            lifetime: Timer::from_seconds(lifetime_secs, TimerMode::Once),
-            color,
-            particle_entities: Vec::new(), // Will be populated after spawn
-            show_gizmos: false,            // Particles by default
-            enable_flicker: false,         // No flicker by default
+        match energy.model {
+            EnergyModel::Interpolated => {}
+            EnergyModel::DownstreamTips => {
+                let branch_tips = self
+                    .tips()
+                    .filter(|idx| !self.strike_points.contains(idx))
+                    .count();
+                let mut current = vec![0.0f32; self.nodes.len()];
+                for &idx in &self.strike_points {
+                    current[idx] = (branch_tips + 1) as f32;
+                }
+                for &idx in order.iter().rev() {
+                    if self.nodes[idx].children.is_empty() && current[idx] == 0.0 {
+                        current[idx] = 1.0;
+                    }
+                    if let Some(parent) = self.nodes[idx].parent {
+                        current[parent] += current[idx];
+                    }
+                }
+                let total = current[0].max(f32::EPSILON);
+                for (node, current) in self.nodes.iter_mut().zip(current) {
+                    node.energy = (current / total).clamp(0.0, 1.0);
+                }
+            }
+            EnergyModel::PathDecay => {
+                let mut distance = vec![0.0f32; self.nodes.len()];
+                for &idx in &order[1..] {
+                    let parent = self.nodes[idx].parent.unwrap_or(0);
+                    distance[idx] = distance[parent]
+                        + self.nodes[idx]
+                            .position
+                            .distance(self.nodes[parent].position);
+                }
+                let main_length = self
+                    .strike_points
+                    .first()
+                    .map_or(0.0, |&idx| distance[idx])
+                    .max(f32::EPSILON);
+                let end_energy = energy.end_energy.clamp(0.0, 1.0);
+                let branch_factor = energy.branch_factor.clamp(0.0, 1.0);
+                for (node, distance) in self.nodes.iter_mut().zip(distance) {
+                    node.energy = (end_energy.powf(distance / main_length)
+                        * branch_factor.powi(node.branch_depth as i32))
+                    .clamp(0.0, 1.0);
+                }
+            }
         }
     }
-}
 
-/// Plugin for procedural lightning system
-pub struct ProceduralLightningPlugin;
+    /// Remove branches shorter than `min_length` or with a tip energy below `min_tip_energy`
+    ///
+    /// A branch's length includes its sub-branches, and removing a branch removes them
+    /// too. The main channel and branches leading to strike points are always kept.
+    /// Nodes and segments are compacted, and indices, strike points and topology are
+    /// updated. Returns the number of removed nodes.
+    pub fn prune(&mut self, min_length: f32, min_tip_energy: f32) -> usize {
+        if self.nodes.is_empty() || (min_length <= 0.0 && min_tip_energy <= 0.0) {
+            return 0;
+        }
 
-impl Plugin for ProceduralLightningPlugin {
-    fn build(&self, app: &mut App) {
-        app.add_systems(
-            Update,
-            (update_procedural_lightning, cleanup_expired_lightning),
+        let order = self.breadth_first_order();
+
+        let mut subtree_length = vec![0.0f32; self.nodes.len()];
+        let mut leads_to_strike = vec![false; self.nodes.len()];
+        for &idx in &self.strike_points {
+            leads_to_strike[idx] = true;
+        }
+        for &idx in order.iter().rev() {
+            if let Some(parent) = self.nodes[idx].parent {
+                subtree_length[parent] += subtree_length[idx]
+                    + self.nodes[idx]
+                        .position
+                        .distance(self.nodes[parent].position);
+                leads_to_strike[parent] |= leads_to_strike[idx];
+            }
+        }
+
+        let mut keep = vec![true; self.nodes.len()];
+        for &idx in &order {
+            let Some(parent) = self.nodes[idx].parent else {
+                continue;
+            };
+            if !keep[parent] {
+                keep[idx] = false;
+                continue;
+            }
+            let branch_id = self.nodes[idx].branch_id;
+            if branch_id == self.nodes[parent].branch_id || leads_to_strike[idx] {
+                continue;
+            }
+
+            // `idx` starts a branch: measure it and find its tip
+            let length = subtree_length[idx]
+                + self.nodes[idx]
+                    .position
+                    .distance(self.nodes[parent].position);
+            let mut tip = idx;
+            while let Some(&next) = self.nodes[tip].children.first() {
+                if self.nodes[next].branch_id != branch_id {
+                    break;
+                }
+                tip = next;
+            }
+            if length < min_length || self.nodes[tip].energy < min_tip_energy {
+                keep[idx] = false;
+            }
+        }
+
+        let removed = keep.iter().filter(|&&kept| !kept).count();
+        if removed == 0 {
+            return 0;
+        }
+
+        let mut remap = vec![usize::MAX; self.nodes.len()];
+        let mut next = 0;
+        for (idx, &kept) in keep.iter().enumerate() {
+            if kept {
+                remap[idx] = next;
+                next += 1;
+            }
+        }
+        self.nodes = std::mem::take(&mut self.nodes)
+            .into_iter()
+            .zip(&keep)
+            .filter_map(|(node, &kept)| kept.then_some(node))
+            .collect();
+        self.segments.retain(|&(_, child)| keep[child]);
+        for (parent, child) in &mut self.segments {
+            *parent = remap[*parent];
+            *child = remap[*child];
+        }
+        for strike_point in &mut self.strike_points {
+            *strike_point = remap[*strike_point];
+        }
+        self.rebuild_topology();
+        removed
+    }
+
+    /// Prune with `config.prune`, then recompute energies for the branches that are left
+    fn prune_for(&mut self, config: &LightningConfig) {
+        if self.prune(config.prune.min_length, config.prune.min_tip_energy) > 0 {
+            // Downstream-tip energy depends on the branches that are left
+            self.apply_energy_model(&config.energy);
+        }
+    }
+
+    /// Time at which the leader reaches the primary strike point and the full channel lights up
+    pub fn strike_time(&self) -> f32 {
+        self.strike_points
+            .first()
+            .map_or(0.0, |&idx| self.nodes[idx].arrival_time)
+    }
+
+    /// Generate a procedural lightning tree from start to end position
+    pub fn generate(start: Vec3, end: Vec3, config: &LightningConfig) -> Self {
+        config.generate_seeded(start, end)
+    }
+
+    /// Recursive midpoint subdivision drawing randomness from `rng`
+    ///
+    /// When `obstacles` is set (with a clearance distance), displaced midpoints and
+    /// branches that would intersect scene geometry are rejected or nudged away.
+    fn subdivide(
+        start: Vec3,
+        end: Vec3,
+        config: &LightningConfig,
+        rng: &mut dyn RngCore,
+        obstacles: Option<(&dyn LightningObstacles, f32)>,
+    ) -> Self {
+        let mut tree = Self::new(start, Vec::new(), Vec::new());
+        tree.subdivide_into(start, end, config, rng, obstacles);
+        tree
+    }
+
+    /// [`LightningTree::subdivide`] reusing this tree's node and segment buffers
+    fn subdivide_into(
+        &mut self,
+        start: Vec3,
+        end: Vec3,
+        config: &LightningConfig,
+        rng: &mut dyn RngCore,
+        obstacles: Option<(&dyn LightningObstacles, f32)>,
+    ) {
+        // Start with the root segment
+        let mut nodes = std::mem::take(&mut self.nodes);
+        nodes.clear();
+        nodes.push(LightningNode::new(start, 0, 1.0));
+        nodes.push(LightningNode::new(end, 0, config.energy.end_energy));
+        let segments = std::mem::take(&mut self.segments);
+        *self =
+            Self::subdivide_skeleton(nodes, segments, &[(0, 1)], vec![1], config, rng, obstacles);
+    }
+
+    /// Generate one leader that forks so that every target is hit by its own channel
+    ///
+    /// Targets are split recursively into two groups; each split forks
+    /// `branch.fork_fraction` of the way toward the group's centroid. The nodes
+    /// hitting the targets are reported in `strike_points`, in the order of `targets`.
+    pub fn generate_forked(start: Vec3, targets: &[Vec3], config: &LightningConfig) -> Self {
+        let mut rng = ChaCha8Rng::seed_from_u64(config.seed);
+        let mut nodes = vec![LightningNode::new(start, 0, 1.0)];
+        let mut skeleton = Vec::new();
+        let mut strike_points = vec![0; targets.len()];
+        let indexed: Vec<(usize, Vec3)> = targets.iter().copied().enumerate().collect();
+        build_fork_skeleton(
+            &mut nodes,
+            &mut skeleton,
+            &mut strike_points,
+            0,
+            indexed,
+            config,
         );
+        Self::subdivide_skeleton(
+            nodes,
+            Vec::new(),
+            &skeleton,
+            strike_points,
+            config,
+            &mut rng,
+            None,
+        )
+    }
+
+    /// Generate two trees with the same topology but different displacement
+    ///
+    /// Both share `config.seed` for branching and use `displacement_seeds` for offsets,
+    /// so they can be blended with [`LightningTree::lerp`]. The topology only matches
+    /// when `target_segment_length` is `None` and `prune.min_length` is 0.0, since
+    /// otherwise segment and branch lengths decide where subdivision stops and which
+    /// branches are kept.
+    pub fn generate_pair(
+        start: Vec3,
+        end: Vec3,
+        config: &LightningConfig,
+        displacement_seeds: [u64; 2],
+    ) -> [Self; 2] {
+        displacement_seeds.map(|seed| {
+            let config = LightningConfig {
+                displacement_seed: Some(seed),
+                ..config.clone()
+            };
+            Self::generate(start, end, &config)
+        })
+    }
+
+    /// Blend node positions, energies and arrival times toward `other` (`t` = 0.0 .. 1.0)
+    ///
+    /// Returns `None` unless both trees have the same segments, e.g. a pair from
+    /// [`LightningTree::generate_pair`].
+    pub fn lerp(&self, other: &Self, t: f32) -> Option<Self> {
+        if self.segments != other.segments || self.nodes.len() != other.nodes.len() {
+            return None;
+        }
+        let mut tree = self.clone();
+        tree.root = self.root.lerp(other.root, t);
+        for (node, target) in tree.nodes.iter_mut().zip(&other.nodes) {
+            node.position = node.position.lerp(target.position, t);
+            node.energy += (target.energy - node.energy) * t;
+            node.arrival_time += (target.arrival_time - node.arrival_time) * t;
+        }
+        Some(tree)
+    }
+
+    /// Subdivide every (start, end) channel of a skeleton, spawning branches along the way
+    ///
+    /// `segments` is cleared and reused as the output buffer.
+    fn subdivide_skeleton(
+        mut nodes: Vec<LightningNode>,
+        mut segments: Vec<(usize, usize)>,
+        skeleton: &[(usize, usize)],
+        strike_points: Vec<usize>,
+        config: &LightningConfig,
+        rng: &mut dyn RngCore,
+        obstacles: Option<(&dyn LightningObstacles, f32)>,
+    ) -> Self {
+        let root = nodes[0].position;
+        let skeleton_nodes = nodes.len();
+
+        // Hierarchical seeding draws one base seed and derives everything else from it,
+        // so different RNGs (e.g. continuous arc re-rolls) still give different bolts
+        let base_seed = match config.seed_mode {
+            SeedMode::Stream => 0,
+            SeedMode::Hierarchical => rng.next_u64(),
+        };
+
+        // Every subdivision adds one node and one segment (two with a branch)
+        let fits = |node_count: usize, segment_count: usize| {
+            config.max_nodes.is_none_or(|max| node_count <= max)
+                && config.max_segments.is_none_or(|max| segment_count <= max)
+        };
+
+        // Subdivide depth-first, which keeps the bolt the same whether or not a budget
+        // is set. If the budget runs out, start over with the main channel and coarse
+        // levels first, so the budget goes where it shows.
+        let mut prioritized = false;
+        let truncated = 'pass: loop {
+            segments.clear();
+            nodes.truncate(skeleton_nodes);
+            let mut truncated = false;
+
+            // With a displacement seed, offsets come from their own stream so that the
+            // branching decisions (and therefore the topology) only depend on `rng`
+            let mut displacement_rng = config.displacement_seed.map(ChaCha8Rng::seed_from_u64);
+
+            let mut to_subdivide = SubdivisionQueue::new(prioritized);
+            for (channel, &(start_idx, end_idx)) in skeleton.iter().enumerate() {
+                let target = nodes[end_idx].position;
+                let chord = target.distance(nodes[start_idx].position);
+                to_subdivide.push((start_idx, end_idx, 0, 0, target, channel as u64, chord));
+            }
+
+            while let Some((start_idx, end_idx, depth, branch_depth, target, key, chord)) =
+                to_subdivide.pop()
+            {
+                let start_pos = nodes[start_idx].position;
+                let end_pos = nodes[end_idx].position;
+                let segment = end_pos - start_pos;
+                let length = segment.length();
+
+                let short_enough = config
+                    .target_segment_length
+                    .is_some_and(|target| length <= target);
+                if depth >= config.max_depth || short_enough {
+                    // Max depth or target length reached, just connect as final segment
+                    segments.push((start_idx, end_idx));
+                    continue;
+                }
+
+                // Out of budget: keep the segment as it is (this one counts as pending too)
+                let pending_segments = segments.len() + to_subdivide.len() + 1;
+                if !fits(nodes.len() + 1, pending_segments + 1) {
+                    if !prioritized {
+                        prioritized = true;
+                        continue 'pass;
+                    }
+                    segments.push((start_idx, end_idx));
+                    truncated = true;
+                    continue;
+                }
+
+                // In hierarchical mode this subdivision draws from RNGs keyed by its path,
+                // so edits elsewhere in the tree don't shift its random numbers
+                let mut node_rng;
+                let mut node_displacement_rng;
+                let (rng, displacement_rng): (&mut dyn RngCore, &mut Option<ChaCha8Rng>) =
+                    match config.seed_mode {
+                        SeedMode::Stream => (&mut *rng, &mut displacement_rng),
+                        SeedMode::Hierarchical => {
+                            node_rng = ChaCha8Rng::seed_from_u64(mix_seed(base_seed, key));
+                            node_displacement_rng = config
+                                .displacement_seed
+                                .map(|seed| ChaCha8Rng::seed_from_u64(mix_seed(seed, key)));
+                            (&mut node_rng, &mut node_displacement_rng)
+                        }
+                    };
+
+                let start_energy = nodes[start_idx].energy;
+                let end_energy = nodes[end_idx].energy;
+
+                // Calculate midpoint with random perpendicular offset
+                let midpoint = (start_pos + end_pos) * 0.5;
+
+                let shape_rng = select_shape_rng(displacement_rng, rng);
+                let perpendicular = match (config.displacement_mode, &config.noise) {
+                    (DisplacementMode::Planar, _) => {
+                        planar_perpendicular(segment, config.displacement_plane)
+                    }
+                    (DisplacementMode::Volumetric, Some(noise)) => {
+                        let (u, v) = segment
+                            .try_normalize()
+                            .unwrap_or(Vec3::Y)
+                            .any_orthonormal_pair();
+                        let angle =
+                            noise.field(midpoint, NOISE_DIRECTION_SHIFT) * std::f32::consts::TAU;
+                        u * angle.cos() + v * angle.sin()
+                    }
+                    (DisplacementMode::Volumetric, None) => {
+                        random_perpendicular(segment, shape_rng)
+                    }
+                };
+
+                // Random displacement: beta controls magnitude, random value controls direction,
+                // and the Hurst exponent rescales it per subdivision level. Rough and smooth
+                // bolts measure it against the undisplaced chord, so earlier offsets don't
+                // compound and the amplitude follows beta * L * 2^(-H * depth). At the default
+                // of 1.0 the segment's own length is kept, so existing bolts don't change.
+                let roughness = 2f32.powf((1.0 - config.hurst_exponent) * depth as f32);
+                let scale = if config.hurst_exponent == 1.0 {
+                    length
+                } else {
+                    chord
+                };
+                let displacement = match &config.noise {
+                    Some(noise) => noise.sample(midpoint),
+                    None => sample_displacement(shape_rng, config.displacement_distribution),
+                };
+                let offset = perpendicular * displacement * roughness * config.beta * scale;
+                let displaced_midpoint = match obstacles {
+                    Some((obstacles, clearance)) => {
+                        route_midpoint(obstacles, clearance, start_pos, end_pos, midpoint, offset)
+                    }
+                    None => midpoint + offset,
+                };
+
+                // Create middle node
+                let mut mid_node = LightningNode::new(
+                    displaced_midpoint,
+                    branch_depth,
+                    (start_energy + end_energy) * 0.5,
+                );
+                mid_node.level = depth + 1;
+                let mid_idx = nodes.len();
+                nodes.push(mid_node.clone());
+
+                // Queue subdivisions for both halves
+                to_subdivide.push((
+                    start_idx,
+                    mid_idx,
+                    depth + 1,
+                    branch_depth,
+                    target,
+                    mix_seed(key, 1),
+                    chord * 0.5,
+                ));
+                to_subdivide.push((
+                    mid_idx,
+                    end_idx,
+                    depth + 1,
+                    branch_depth,
+                    target,
+                    mix_seed(key, 2),
+                    chord * 0.5,
+                ));
+
+                // Branch generation with probability decay
+                if branch_depth < config.max_branch_depth {
+                    let branch_prob = config.gamma
+                        * f32::exp(-config.alpha * (depth as f32 + branch_depth as f32));
+
+                    let spawn_branch = rng.gen::<f32>() < branch_prob;
+                    let branch_fits =
+                        fits(nodes.len() + 1, segments.len() + to_subdivide.len() + 1);
+                    if spawn_branch && !branch_fits && !prioritized {
+                        prioritized = true;
+                        continue 'pass;
+                    }
+                    truncated |= spawn_branch && !branch_fits;
+                    if spawn_branch && branch_fits {
+                        // Spawn a branch from the midpoint
+                        let branch_length = length
+                            * config.branch.length_factor
+                            * f32::exp(-config.alpha * branch_depth as f32);
+
+                        // Calculate overall direction to the channel's target (end point)
+                        let to_target = (target - displaced_midpoint).normalize();
+
+                        // Random branch direction: blend perpendicular offset with forward direction
+                        // This ensures branches generally move toward the target
+                        let shape_rng = select_shape_rng(displacement_rng, rng);
+                        let branch_perpendicular = match config.displacement_mode {
+                            DisplacementMode::Planar => perpendicular,
+                            DisplacementMode::Volumetric => {
+                                random_perpendicular(segment, shape_rng)
+                            }
+                        };
+                        let spread = config.branch.perpendicular_spread;
+                        let perp_component =
+                            branch_perpendicular * sample_range(shape_rng, -spread, spread);
+                        let forward_component = to_target
+                            * sample_range(
+                                shape_rng,
+                                config.branch.forward_min,
+                                config.branch.forward_max,
+                            );
+                        // With no spread or forward component, head straight for the target
+                        let branch_dir =
+                            (perp_component + forward_component).normalize_or(to_target);
+
+                        let branch_end_pos = match obstacles {
+                            Some((obstacles, clearance)) => clear_branch_end(
+                                obstacles,
+                                clearance,
+                                displaced_midpoint,
+                                branch_dir * branch_length,
+                            ),
+                            None => Some(displaced_midpoint + branch_dir * branch_length),
+                        };
+
+                        // Branches fully blocked by geometry are dropped
+                        if let Some(branch_end_pos) = branch_end_pos {
+                            // Branches have lower energy
+                            let mut branch_end_node = LightningNode::new(
+                                branch_end_pos,
+                                branch_depth + 1,
+                                mid_node.energy * config.energy.branch_factor,
+                            );
+                            branch_end_node.level = depth + 1;
+                            let branch_end_idx = nodes.len();
+                            nodes.push(branch_end_node);
+
+                            // Queue branch for subdivision
+                            to_subdivide.push((
+                                mid_idx,
+                                branch_end_idx,
+                                depth + 1,
+                                branch_depth + 1,
+                                target,
+                                mix_seed(key, 3),
+                                branch_end_pos.distance(displaced_midpoint),
+                            ));
+                        }
+                    }
+                }
+            }
+
+            break truncated;
+        };
+
+        let mut tree = Self::with_strike_points(root, nodes, segments, strike_points);
+        tree.truncated = truncated;
+        tree.apply_energy_model(&config.energy);
+        tree.prune_for(config);
+        tree.assign_arrival_times(&config.growth, config.seed);
+        tree
+    }
+
+    /// Generate a lightning tree with the Dielectric Breakdown Model
+    ///
+    /// The channel grows cell by cell on a grid spanning start -> end. After each
+    /// step the Laplace equation is relaxed with the channel held at potential 0 and
+    /// the end cell at potential 1, and the next cell is picked among the channel's
+    /// neighbours with probability proportional to `potential^eta`.
+    pub fn generate_dbm(start: Vec3, end: Vec3, config: &DbmConfig) -> Self {
+        config.generate_seeded(start, end)
+    }
+
+    /// Laplacian growth drawing randomness from `rng`
+    fn grow_dbm(start: Vec3, end: Vec3, config: &DbmConfig, rng: &mut dyn RngCore) -> Self {
+        let n = config.resolution.max(1) as usize;
+        let axis = end - start;
+        let length = axis.length();
+        if length < f32::EPSILON {
+            return Self::new(start, vec![LightningNode::new(start, 0, 1.0)], Vec::new());
+        }
+
+        // Grid frame: `a` runs along the bolt axis, `b` and `c` across it
+        let forward = axis / length;
+        let (side, up) = match config.grid {
+            DisplacementMode::Planar => (planar_perpendicular(axis, config.plane), Vec3::ZERO),
+            DisplacementMode::Volumetric => forward.any_orthonormal_pair(),
+        };
+        // Volumetric grids are kept narrower, relaxation cost grows with the cell count
+        let half_width = match config.grid {
+            DisplacementMode::Planar => n / 2,
+            DisplacementMode::Volumetric => n / 4,
+        }
+        .max(1);
+        let depth_cells = match config.grid {
+            DisplacementMode::Planar => 1,
+            DisplacementMode::Volumetric => 2 * half_width + 1,
+        };
+        let mut grid = DbmGrid::new([n + 1, 2 * half_width + 1, depth_cells]);
+        let cell_size = length / n as f32;
+        let center = [0, half_width, depth_cells / 2];
+        let cell_position = |[a, b, c]: [usize; 3]| {
+            start
+                + forward * (a as f32 * cell_size)
+                + side * ((b as f32 - center[1] as f32) * cell_size)
+                + up * ((c as f32 - center[2] as f32) * cell_size)
+        };
+
+        let root_cell = grid.index(center);
+        let target_cell = grid.index([n, center[1], center[2]]);
+        grid.state[target_cell] = DbmCell::Target;
+
+        // Channel cells in growth order with the node index of their parent
+        let mut channel: Vec<(usize, Option<usize>)> = vec![(root_cell, None)];
+        let mut node_of_cell = vec![usize::MAX; grid.potential.len()];
+        node_of_cell[root_cell] = 0;
+        grid.state[root_cell] = DbmCell::Channel;
+        grid.potential[root_cell] = 0.0;
+
+        let mut candidates = Vec::new();
+        let mut is_candidate = vec![false; grid.potential.len()];
+        grid.push_candidates(root_cell, &mut candidates, &mut is_candidate);
+        grid.relax(config.relaxation_iterations);
+
+        let mut reached_target = false;
+        while !candidates.is_empty() {
+            // Pick a growth cell weighted by potential^eta
+            let weights: Vec<f32> = candidates
+                .iter()
+                .map(|&cell| grid.potential[cell].max(0.0).powf(config.eta))
+                .collect();
+            let total: f32 = weights.iter().sum();
+            let pick = if total > f32::EPSILON {
+                let mut threshold = rng.gen::<f32>() * total;
+                weights
+                    .iter()
+                    .position(|&w| {
+                        threshold -= w;
+                        threshold <= 0.0
+                    })
+                    .unwrap_or(weights.len() - 1)
+            } else {
+                rng.gen_range(0..candidates.len())
+            };
+            let cell = candidates.swap_remove(pick);
+            is_candidate[cell] = false;
+
+            // Attach to the closest neighbouring channel cell
+            let parent_cell = grid
+                .neighbors(cell)
+                .filter(|&other| grid.state[other] == DbmCell::Channel)
+                .min_by_key(|&other| grid.lattice_distance_squared(cell, other))
+                .expect("candidate cells always touch the channel");
+
+            node_of_cell[cell] = channel.len();
+            channel.push((cell, Some(node_of_cell[parent_cell])));
+
+            if grid.state[cell] == DbmCell::Target {
+                reached_target = true;
+                break;
+            }
+
+            grid.state[cell] = DbmCell::Channel;
+            grid.potential[cell] = 0.0;
+            grid.push_candidates(cell, &mut candidates, &mut is_candidate);
+            grid.relax(config.relaxation_iterations);
+        }
+
+        let mut nodes: Vec<LightningNode> = channel
+            .iter()
+            .map(|&(cell, _)| LightningNode::new(cell_position(grid.coords(cell)), 0, 0.0))
+            .collect();
+        let mut parents: Vec<Option<usize>> = channel.iter().map(|&(_, parent)| parent).collect();
+
+        let end_idx = if reached_target {
+            let last = nodes.len() - 1;
+            nodes[last].position = end;
+            last
+        } else {
+            // Grid exhausted without touching the target, bridge from the closest cell
+            let closest = nodes
+                .iter()
+                .enumerate()
+                .min_by(|(_, a), (_, b)| {
+                    a.position
+                        .distance_squared(end)
+                        .total_cmp(&b.position.distance_squared(end))
+                })
+                .map_or(0, |(idx, _)| idx);
+            nodes.push(LightningNode::new(end, 0, 0.0));
+            parents.push(Some(closest));
+            nodes.len() - 1
+        };
+
+        // Subtree sizes (parents are always created before their children)
+        let mut subtree_size = vec![1usize; nodes.len()];
+        for idx in (1..nodes.len()).rev() {
+            if let Some(parent) = parents[idx] {
+                subtree_size[parent] += subtree_size[idx];
+            }
+        }
+
+        // Main channel runs from the root to the target
+        let mut on_main_path = vec![false; nodes.len()];
+        let mut cursor = Some(end_idx);
+        while let Some(idx) = cursor {
+            on_main_path[idx] = true;
+            cursor = parents[idx];
+        }
+
+        // Each node continues its parent's branch through the largest child subtree
+        let mut continuation = vec![usize::MAX; nodes.len()];
+        for idx in 1..nodes.len() {
+            let Some(parent) = parents[idx] else {
+                continue;
+            };
+            let current = continuation[parent];
+            let better = if on_main_path[parent] {
+                on_main_path[idx]
+            } else {
+                current == usize::MAX || subtree_size[idx] > subtree_size[current]
+            };
+            if better {
+                continuation[parent] = idx;
+            }
+        }
+
+        let mut segments = Vec::with_capacity(nodes.len() - 1);
+        for idx in 1..nodes.len() {
+            let Some(parent) = parents[idx] else {
+                continue;
+            };
+            let branch_depth = if continuation[parent] == idx {
+                nodes[parent].branch_depth
+            } else {
+                nodes[parent].branch_depth + 1
+            };
+            // Energy fades from 1.0 at the root to `end_energy` at the target,
+            // scaled by `branch_factor` per branch level
+            let progress = (nodes[idx].position - start).dot(forward) / length;
+            nodes[idx].branch_depth = branch_depth;
+            nodes[idx].energy = (1.0 - (1.0 - config.energy.end_energy) * progress.clamp(0.0, 1.0))
+                * config.energy.branch_factor.powi(branch_depth as i32);
+            segments.push((parent, idx));
+        }
+        nodes[0].energy = 1.0;
+
+        let mut tree = Self::with_strike_points(start, nodes, segments, vec![end_idx]);
+        tree.apply_energy_model(&config.energy);
+        tree.assign_arrival_times(&config.growth, config.seed);
+        tree
+    }
+
+    /// Finest subdivision level present in the tree
+    pub fn max_level(&self) -> u32 {
+        self.nodes.iter().map(|node| node.level).max().unwrap_or(0)
+    }
+
+    /// Segments of the tree cut at subdivision `level`, as (parent, child) pairs
+    ///
+    /// Nodes finer than `level` are skipped and every remaining node connects to its
+    /// closest remaining ancestor, so the silhouette is kept with fewer segments.
+    pub fn detail_segments(&self, level: u32) -> impl Iterator<Item = (usize, usize)> + '_ {
+        self.segments.iter().filter_map(move |&(_, child)| {
+            if self.nodes[child].level > level {
+                return None;
+            }
+            let mut parent = self.nodes[child].parent?;
+            while self.nodes[parent].level > level {
+                parent = self.nodes[parent].parent?;
+            }
+            Some((parent, child))
+        })
+    }
+
+    /// A copy of the tree with only the nodes up to subdivision `level`
+    ///
+    /// With [`SeedMode::Hierarchical`] this matches generating with `max_depth = level`.
+    /// Trees without subdivision levels (e.g. from DBM) are returned unchanged.
+    #[must_use]
+    pub fn at_detail_level(&self, level: u32) -> Self {
+        let mut remap = vec![usize::MAX; self.nodes.len()];
+        let mut nodes = Vec::new();
+        for (idx, node) in self.nodes.iter().enumerate() {
+            if node.level <= level {
+                remap[idx] = nodes.len();
+                nodes.push(node.clone());
+            }
+        }
+        let segments = self
+            .detail_segments(level)
+            .map(|(parent, child)| (remap[parent], remap[child]))
+            .collect();
+        let strike_points = self
+            .strike_points
+            .iter()
+            .map(|&idx| remap[idx])
+            .filter(|&idx| idx != usize::MAX)
+            .collect();
+        let mut tree = Self::with_strike_points(self.root, nodes, segments, strike_points);
+        tree.truncated = self.truncated;
+        tree
+    }
+
+    /// Map the tree onto new endpoints, e.g. to reuse a cached shape for any strike
+    ///
+    /// The root -> primary strike point axis is rotated onto `start -> end` and
+    /// stretched to its length. Sideways offsets are scaled by the same ratio, so
+    /// branches and jaggedness keep their proportions. Arrival times are kept; call
+    /// [`LightningTree::assign_arrival_times`] to re-time the bolt for its new length.
+    pub fn retarget(&mut self, start: Vec3, end: Vec3) {
+        self.warp_endpoints(start, end, true);
+    }
+
+    /// Like [`LightningTree::retarget`], but only stretch along the axis
+    ///
+    /// Sideways offsets keep their world-space size, so a long stretch looks straighter.
+    pub fn stretch_to(&mut self, start: Vec3, end: Vec3) {
+        self.warp_endpoints(start, end, false);
+    }
+
+    /// A copy of this tree mapped onto new endpoints with [`LightningTree::retarget`]
+    #[must_use]
+    pub fn retargeted(&self, start: Vec3, end: Vec3) -> Self {
+        let mut tree = self.clone();
+        tree.retarget(start, end);
+        tree
+    }
+
+    fn warp_endpoints(&mut self, start: Vec3, end: Vec3, proportional: bool) {
+        let Some(&end_idx) = self.strike_points.first() else {
+            return;
+        };
+        let old_start = self.nodes[0].position;
+        let old_end = self.nodes[end_idx].position;
+        let mut warp = AxisWarp::new(old_start, old_end, start, end, 1.0);
+        if proportional {
+            warp.lateral_scale = warp.axial_scale;
+        }
+        self.warp(&warp);
+    }
+
+    /// Move every node (and the root) through `warp`
+    fn warp(&mut self, warp: &AxisWarp) {
+        self.root = warp.point(self.root);
+        for node in &mut self.nodes {
+            node.position = warp.point(node.position);
+        }
+    }
+
+    /// Get the total number of segments in the tree
+    pub fn segment_count(&self) -> usize {
+        self.segments.len()
+    }
+
+    /// Get all line positions for rendering as a line strip or gizmos
+    pub fn get_line_positions(&self) -> Vec<Vec3> {
+        self.segments
+            .iter()
+            .flat_map(|(start_idx, end_idx)| {
+                [
+                    self.nodes[*start_idx].position,
+                    self.nodes[*end_idx].position,
+                ]
+            })
+            .collect()
+    }
+
+    /// Sample positions along the lightning path for particle spawning
+    /// Returns positions evenly spaced along the lightning tree
+    pub fn sample_particle_positions(&self, particle_count: usize) -> Vec<Vec3> {
+        if self.segments.is_empty() {
+            return Vec::new();
+        }
+
+        let mut positions = Vec::with_capacity(particle_count);
+        let segments_per_particle = self.segments.len().max(1) / particle_count.max(1);
+
+        for (i, (start_idx, end_idx)) in self.segments.iter().enumerate() {
+            if i % segments_per_particle == 0 && positions.len() < particle_count {
+                // Interpolate along the segment
+                let t = 0.5; // Use midpoint
+                let start_pos = self.nodes[*start_idx].position;
+                let end_pos = self.nodes[*end_idx].position;
+                positions.push(start_pos.lerp(end_pos, t));
+            }
+        }
+
+        positions
+    }
+
+    /// Get energy-weighted positions for particle brightness
+    pub fn get_particle_data(&self, particle_count: usize) -> Vec<(Vec3, f32)> {
+        if self.segments.is_empty() {
+            return Vec::new();
+        }
+
+        let mut data = Vec::with_capacity(particle_count);
+        let segments_per_particle = self.segments.len().max(1) / particle_count.max(1);
+
+        for (i, (start_idx, end_idx)) in self.segments.iter().enumerate() {
+            if i % segments_per_particle == 0 && data.len() < particle_count {
+                let start_node = &self.nodes[*start_idx];
+                let end_node = &self.nodes[*end_idx];
+                let t = 0.5;
+                let position = start_node.position.lerp(end_node.position, t);
+                let energy = start_node.energy * (1.0 - t) + end_node.energy * t;
+                data.push((position, energy));
+            }
+        }
+
+        data
+    }
+}
+
+/// Segment waiting to be subdivided:
+/// (start_idx, end_idx, current_depth, branch_depth, channel target, path key,
+/// undisplaced chord length)
+type PendingSegment = (usize, usize, u32, u32, Vec3, u64, f32);
+
+/// Work queue for subdivision
+///
+/// Normally a plain stack (depth-first). Once a budget has run out, prioritized:
+/// the main channel and coarse levels are subdivided first.
+enum SubdivisionQueue {
+    Stack(Vec<PendingSegment>),
+    Prioritized {
+        // (branch_depth, depth, insertion order) -> index into `entries`
+        heap: BinaryHeap<Reverse<(u32, u32, usize)>>,
+        entries: Vec<PendingSegment>,
+    },
+}
+
+impl SubdivisionQueue {
+    fn new(prioritized: bool) -> Self {
+        if prioritized {
+            Self::Prioritized {
+                heap: BinaryHeap::new(),
+                entries: Vec::new(),
+            }
+        } else {
+            Self::Stack(Vec::new())
+        }
+    }
+
+    fn push(&mut self, entry: PendingSegment) {
+        match self {
+            Self::Stack(stack) => stack.push(entry),
+            Self::Prioritized { heap, entries } => {
+                heap.push(Reverse((entry.3, entry.2, entries.len())));
+                entries.push(entry);
+            }
+        }
+    }
+
+    fn pop(&mut self) -> Option<PendingSegment> {
+        match self {
+            Self::Stack(stack) => stack.pop(),
+            Self::Prioritized { heap, entries } => {
+                heap.pop().map(|Reverse((_, _, index))| entries[index])
+            }
+        }
+    }
+
+    fn len(&self) -> usize {
+        match self {
+            Self::Stack(stack) => stack.len(),
+            Self::Prioritized { heap, .. } => heap.len(),
+        }
     }
 }
 
-/// Update procedural lightning animations and rendering
-#[allow(clippy::needless_pass_by_value)]
-fn update_procedural_lightning(
-    mut query: Query<(&mut ProceduralLightning, &Transform)>,
-    time: Res<Time>,
-    mut gizmos: Gizmos,
-) {
-    for (mut lightning, transform) in &mut query {
-        lightning.animation_timer.tick(time.delta());
-        lightning.lifetime.tick(time.delta());
+/// Maps a start -> end axis onto another: rotates the axis, stretches along it and
+/// scales the sideways offsets by `lateral_scale`
+struct AxisWarp {
+    old_start: Vec3,
+    new_start: Vec3,
+    axis: Vec3,
+    rotation: Quat,
+    axial_scale: f32,
+    lateral_scale: f32,
+}
+
+impl AxisWarp {
+    fn new(
+        old_start: Vec3,
+        old_end: Vec3,
+        new_start: Vec3,
+        new_end: Vec3,
+        lateral_scale: f32,
+    ) -> Self {
+        let old_axis = old_end - old_start;
+        let new_axis = new_end - new_start;
+        // A degenerate axis can only be translated
+        let (axis, rotation, axial_scale) =
+            match (old_axis.try_normalize(), new_axis.try_normalize()) {
+                (Some(from), Some(to)) => (
+                    from,
+                    Quat::from_rotation_arc(from, to),
+                    new_axis.length() / old_axis.length(),
+                ),
+                _ => (Vec3::ZERO, Quat::IDENTITY, 1.0),
+            };
+        Self {
+            old_start,
+            new_start,
+            axis,
+            rotation,
+            axial_scale,
+            lateral_scale,
+        }
+    }
+
+    fn point(&self, point: Vec3) -> Vec3 {
+        let offset = point - self.old_start;
+        let along = offset.dot(self.axis);
+        let lateral = offset - self.axis * along;
+        self.new_start
+            + self.rotation * (self.axis * along * self.axial_scale + lateral * self.lateral_scale)
+    }
+}
+
+/// Perpendicular vector lying in the displacement plane
+fn planar_perpendicular(segment: Vec3, plane: DisplacementPlane) -> Vec3 {
+    // Normalized first, so short segments (e.g. in unit-space cached bolts) aren't
+    // mistaken for ones along the normal
+    let perpendicular = segment.normalize_or_zero().cross(plane.normal());
+    if perpendicular.length_squared() > 1e-4 {
+        perpendicular.normalize()
+    } else {
+        // Segment runs along the plane normal, pick a fixed in-plane axis
+        plane.fallback_axis()
+    }
+}
+
+/// Recursively lay out the straight channels of a multi-target strike
+///
+/// Adds a channel from `parent` to each target, forking toward the centroid of each
+/// group of targets. `targets` carries each target's index into `strike_points`.
+fn build_fork_skeleton(
+    nodes: &mut Vec<LightningNode>,
+    skeleton: &mut Vec<(usize, usize)>,
+    strike_points: &mut [usize],
+    parent: usize,
+    mut targets: Vec<(usize, Vec3)>,
+    config: &LightningConfig,
+) {
+    let end_energy = config.energy.end_energy;
+    if let [(target_idx, position)] = targets[..] {
+        let idx = nodes.len();
+        nodes.push(LightningNode::new(position, 0, end_energy));
+        skeleton.push((parent, idx));
+        strike_points[target_idx] = idx;
+        return;
+    }
+    if targets.is_empty() {
+        return;
+    }
+
+    let parent_node = &nodes[parent];
+    let centroid = targets.iter().map(|(_, p)| *p).sum::<Vec3>() / targets.len() as f32;
+    let fork_position = parent_node
+        .position
+        .lerp(centroid, config.branch.fork_fraction);
+    let fork_energy = parent_node
+        .energy
+        .lerp(end_energy, config.branch.fork_fraction);
+    let fork = nodes.len();
+    nodes.push(LightningNode::new(fork_position, 0, fork_energy));
+    skeleton.push((parent, fork));
+
+    // Split along the axis between the two most distant targets
+    let mut spread_axis = Vec3::ZERO;
+    for (i, (_, a)) in targets.iter().enumerate() {
+        for (_, b) in &targets[i + 1..] {
+            if a.distance_squared(*b) > spread_axis.length_squared() {
+                spread_axis = *b - *a;
+            }
+        }
+    }
+    targets.sort_by(|(ia, a), (ib, b)| {
+        a.dot(spread_axis)
+            .total_cmp(&b.dot(spread_axis))
+            .then(ia.cmp(ib))
+    });
+    let second = targets.split_off(targets.len() / 2);
+
+    // Keep the group holding the first target first, so it ends up on the main channel
+    let (first, second) = if second.iter().any(|(idx, _)| *idx == 0) {
+        (second, targets)
+    } else {
+        (targets, second)
+    };
+    build_fork_skeleton(nodes, skeleton, strike_points, fork, first, config);
+    build_fork_skeleton(nodes, skeleton, strike_points, fork, second, config);
+}
+
+/// Mixed into the seed of the arrival-time RNG so it is independent of the shape RNG
+const GROWTH_SEED_SALT: u64 = 0x9E37_79B9_7F4A_7C15;
+
+/// Random displacement factor with a typical magnitude around 0.5
+fn sample_displacement(rng: &mut dyn RngCore, distribution: DisplacementDistribution) -> f32 {
+    match distribution {
+        DisplacementDistribution::Uniform => rng.gen_range(-1.0..1.0),
+        DisplacementDistribution::Gaussian => {
+            // Box-Muller, with the same standard deviation as the uniform case
+            let radius = (-2.0 * (1.0 - rng.gen::<f32>()).ln()).sqrt();
+            let angle = rng.gen_range(0.0..std::f32::consts::TAU);
+            radius * angle.cos() / 3f32.sqrt()
+        }
+        DisplacementDistribution::Cauchy => {
+            // Median magnitude 0.5 like the uniform case; tails clamped so bolts stay bounded
+            let quantile = rng.gen_range(-0.5..0.5) * std::f32::consts::PI;
+            (0.5 * quantile.tan()).clamp(-3.0, 3.0)
+        }
+    }
+}
+
+/// Combine a seed with a key into a well-mixed seed (SplitMix64 finalizer)
+fn mix_seed(seed: u64, key: u64) -> u64 {
+    let mut z = seed ^ key.wrapping_add(1).wrapping_mul(0x9E37_79B9_7F4A_7C15);
+    z = (z ^ (z >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+    z = (z ^ (z >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+    z ^ (z >> 31)
+}
+
+/// RNG for shape randomness: the displacement stream if there is one, else the main RNG
+fn select_shape_rng<'a>(
+    displacement_rng: &'a mut Option<ChaCha8Rng>,
+    rng: &'a mut dyn RngCore,
+) -> &'a mut dyn RngCore {
+    match displacement_rng {
+        Some(displacement_rng) => displacement_rng,
+        None => rng,
+    }
+}
+
+/// Uniform sample in `min..max`, or `min` when the range is empty
+fn sample_range(rng: &mut dyn RngCore, min: f32, max: f32) -> f32 {
+    if max > min {
+        rng.gen_range(min..max)
+    } else {
+        min
+    }
+}
+
+/// Random unit vector in the plane perpendicular to the segment axis
+fn random_perpendicular(segment: Vec3, rng: &mut dyn RngCore) -> Vec3 {
+    let axis = segment.try_normalize().unwrap_or(Vec3::Y);
+    let (u, v) = axis.any_orthonormal_pair();
+    let angle = rng.gen_range(0.0..std::f32::consts::TAU);
+    u * angle.cos() + v * angle.sin()
+}
+
+/// Unit gradients on the cube's edge midpoints, as in improved Perlin noise
+const NOISE_GRADIENTS: [Vec3; 12] = [
+    Vec3::new(1.0, 1.0, 0.0),
+    Vec3::new(-1.0, 1.0, 0.0),
+    Vec3::new(1.0, -1.0, 0.0),
+    Vec3::new(-1.0, -1.0, 0.0),
+    Vec3::new(1.0, 0.0, 1.0),
+    Vec3::new(-1.0, 0.0, 1.0),
+    Vec3::new(1.0, 0.0, -1.0),
+    Vec3::new(-1.0, 0.0, -1.0),
+    Vec3::new(0.0, 1.0, 1.0),
+    Vec3::new(0.0, -1.0, 1.0),
+    Vec3::new(0.0, 1.0, -1.0),
+    Vec3::new(0.0, -1.0, -1.0),
+];
+
+/// Shift between fBm octaves, so their lattices never line up
+const NOISE_OCTAVE_SHIFT: Vec3 = Vec3::new(0.413, 0.529, 0.741);
+
+/// Scales fBm output to the spread of uniform displacement
+const NOISE_DISPLACEMENT_SCALE: f32 = 3.5;
+
+/// Shift into the noise field for volumetric offset directions, so they are
+/// independent of the offset magnitudes sampled at the same midpoint
+const NOISE_DIRECTION_SHIFT: f32 = 57.0;
+
+/// Noise offset between [`LightningCache`] variants, so each samples its own region
+const NOISE_VARIANT_SHIFT: Vec3 = Vec3::new(101.3, 37.9, 71.7);
+
+/// 3D gradient noise in roughly `-1.0..1.0`, smooth and zero at integer lattice points
+fn perlin_noise(point: Vec3) -> f32 {
+    let cell = point.floor();
+    let base = cell.as_ivec3();
+    let local = point - cell;
+    let fade = |t: f32| t * t * t * (t * (t * 6.0 - 15.0) + 10.0);
+    let weight = Vec3::new(fade(local.x), fade(local.y), fade(local.z));
+
+    let mut value = 0.0;
+    for corner in 0..8 {
+        let offset = IVec3::new(corner & 1, (corner >> 1) & 1, (corner >> 2) & 1);
+        let lattice = base + offset;
+        let mut hash = (lattice.x as u32).wrapping_mul(0x8DA6_B343)
+            ^ (lattice.y as u32).wrapping_mul(0xD816_3841)
+            ^ (lattice.z as u32).wrapping_mul(0xCB1A_B31F);
+        hash ^= hash >> 13;
+        hash = hash.wrapping_mul(0x5BD1_E995);
+        hash ^= hash >> 15;
+        let gradient = NOISE_GRADIENTS[(hash % 12) as usize];
+
+        let corner_weight = Vec3::select(offset.cmpeq(IVec3::ONE), weight, Vec3::ONE - weight);
+        value += corner_weight.x
+            * corner_weight.y
+            * corner_weight.z
+            * gradient.dot(local - offset.as_vec3());
+    }
+    value
+}
+
+/// Algorithm that builds a [`LightningTree`] between two points
+///
+/// Implement this to plug custom algorithms into [`ProceduralLightning`] and
+/// [`spawn_procedural_lightning`] without forking the crate. [`LightningConfig`]
+/// (recursive subdivision, the default) and [`DbmConfig`] are the built-in generators.
+pub trait LightningGenerator: Send + Sync {
+    /// Build a tree from `start` to `end`, drawing all randomness from `rng`
+    fn generate(&self, start: Vec3, end: Vec3, rng: &mut dyn RngCore) -> LightningTree;
+
+    /// Seed for the RNG created by [`LightningGenerator::generate_seeded`]
+    fn seed(&self) -> u64 {
+        0
+    }
+
+    /// Build a tree with a deterministic RNG seeded from [`LightningGenerator::seed`]
+    fn generate_seeded(&self, start: Vec3, end: Vec3) -> LightningTree {
+        let mut rng = ChaCha8Rng::seed_from_u64(self.seed());
+        self.generate(start, end, &mut rng)
+    }
+
+    /// Rebuild `tree` in place, reusing its allocations where the generator supports it
+    ///
+    /// Used by [`ContinuousArc`] to re-roll a persistent arc. The default replaces the
+    /// tree with the result of [`LightningGenerator::generate`].
+    fn generate_into(
+        &self,
+        start: Vec3,
+        end: Vec3,
+        rng: &mut dyn RngCore,
+        tree: &mut LightningTree,
+    ) {
+        *tree = self.generate(start, end, rng);
+    }
+}
+
+impl LightningGenerator for LightningConfig {
+    fn generate(&self, start: Vec3, end: Vec3, rng: &mut dyn RngCore) -> LightningTree {
+        LightningTree::subdivide(start, end, self, rng, None)
+    }
+
+    fn generate_into(
+        &self,
+        start: Vec3,
+        end: Vec3,
+        rng: &mut dyn RngCore,
+        tree: &mut LightningTree,
+    ) {
+        tree.subdivide_into(start, end, self, rng, None);
+    }
+
+    fn seed(&self) -> u64 {
+        self.seed
+    }
+}
+
+impl LightningGenerator for DbmConfig {
+    fn generate(&self, start: Vec3, end: Vec3, rng: &mut dyn RngCore) -> LightningTree {
+        LightningTree::grow_dbm(start, end, self, rng)
+    }
+
+    fn seed(&self) -> u64 {
+        self.seed
+    }
+}
+
+/// Scene geometry that generated bolts should route around
+///
+/// `distance` is a signed distance field: positive outside geometry, negative inside.
+/// Closures `Fn(Vec3) -> f32` implement this trait directly. Ray-cast based queries
+/// (e.g. a snapshot of `MeshRayCast` hits) can override
+/// [`LightningObstacles::segment_is_clear`] instead and return `f32::INFINITY` from
+/// `distance`.
+pub trait LightningObstacles: Send + Sync {
+    /// Signed distance from `point` to the nearest surface
+    fn distance(&self, point: Vec3) -> f32;
+
+    /// Whether the segment `a -> b` keeps at least `clearance` from every surface
+    ///
+    /// The required clearance tapers to zero at both endpoints so bolts can still
+    /// touch targets that sit on a surface.
+    fn segment_is_clear(&self, a: Vec3, b: Vec3, clearance: f32) -> bool {
+        let length = a.distance(b);
+        if length < f32::EPSILON {
+            return self.distance(a) >= 0.0;
+        }
+        let direction = (b - a) / length;
+        let min_step = (length * 0.01).max(1e-4);
+
+        // Sphere trace; `distance - required` is 2-Lipschitz so half of it is a safe step
+        let mut t = 0.0;
+        while t <= length {
+            let required = clearance.min(t).min(length - t);
+            let margin = self.distance(a + direction * t) - required;
+            if margin < 0.0 {
+                return false;
+            }
+            t += (margin * 0.5).max(min_step);
+        }
+        self.distance(b) >= 0.0
+    }
+}
+
+impl<F: Fn(Vec3) -> f32 + Send + Sync> LightningObstacles for F {
+    fn distance(&self, point: Vec3) -> f32 {
+        self(point)
+    }
+}
+
+/// Subdivision generator that routes bolts around scene geometry
+///
+/// Displaced midpoints that would cut through geometry are flipped or shrunk, and
+/// pushed out of the surface as a last resort. Blocked branches are shortened or dropped.
+pub struct AvoidObstacles<O: LightningObstacles> {
+    /// Subdivision parameters
+    pub config: LightningConfig,
+    /// Scene geometry to avoid
+    pub obstacles: O,
+    /// Minimum distance kept between the bolt and any surface
+    pub clearance: f32,
+}
+
+impl<O: LightningObstacles> LightningGenerator for AvoidObstacles<O> {
+    fn generate(&self, start: Vec3, end: Vec3, rng: &mut dyn RngCore) -> LightningTree {
+        LightningTree::subdivide(
+            start,
+            end,
+            &self.config,
+            rng,
+            Some((&self.obstacles, self.clearance)),
+        )
+    }
+
+    fn generate_into(
+        &self,
+        start: Vec3,
+        end: Vec3,
+        rng: &mut dyn RngCore,
+        tree: &mut LightningTree,
+    ) {
+        tree.subdivide_into(
+            start,
+            end,
+            &self.config,
+            rng,
+            Some((&self.obstacles, self.clearance)),
+        );
+    }
+
+    fn seed(&self) -> u64 {
+        self.config.seed
+    }
+}
+
+/// Displacement scales tried, in order, when a midpoint offset hits geometry
+const OBSTACLE_DISPLACEMENT_STEPS: [f32; 7] = [1.0, -1.0, 0.5, -0.5, 0.25, -0.25, 0.0];
+
+/// Pick a midpoint whose two half-segments avoid the obstacles
+fn route_midpoint(
+    obstacles: &dyn LightningObstacles,
+    clearance: f32,
+    start: Vec3,
+    end: Vec3,
+    midpoint: Vec3,
+    offset: Vec3,
+) -> Vec3 {
+    OBSTACLE_DISPLACEMENT_STEPS
+        .iter()
+        .map(|scale| midpoint + offset * *scale)
+        .find(|&candidate| {
+            obstacles.distance(candidate) >= clearance
+                && obstacles.segment_is_clear(start, candidate, clearance)
+                && obstacles.segment_is_clear(candidate, end, clearance)
+        })
+        .unwrap_or_else(|| push_out_of_obstacles(obstacles, clearance, midpoint + offset))
+}
+
+/// Shorten a branch until it stops hitting geometry, or drop it entirely
+fn clear_branch_end(
+    obstacles: &dyn LightningObstacles,
+    clearance: f32,
+    origin: Vec3,
+    branch: Vec3,
+) -> Option<Vec3> {
+    [1.0, 0.5, 0.25]
+        .iter()
+        .map(|scale| origin + branch * *scale)
+        .find(|&tip| {
+            obstacles.distance(tip) >= clearance
+                && obstacles.segment_is_clear(origin, tip, clearance)
+        })
+}
+
+/// Nudge a point along the distance gradient until it clears the surface
+fn push_out_of_obstacles(obstacles: &dyn LightningObstacles, clearance: f32, point: Vec3) -> Vec3 {
+    let epsilon = (clearance * 0.5).max(1e-3);
+    let mut point = point;
+    for _ in 0..4 {
+        let distance = obstacles.distance(point);
+        if distance >= clearance {
+            break;
+        }
+        let gradient = Vec3::new(
+            obstacles.distance(point + Vec3::X * epsilon)
+                - obstacles.distance(point - Vec3::X * epsilon),
+            obstacles.distance(point + Vec3::Y * epsilon)
+                - obstacles.distance(point - Vec3::Y * epsilon),
+            obstacles.distance(point + Vec3::Z * epsilon)
+                - obstacles.distance(point - Vec3::Z * epsilon),
+        )
+        .normalize_or_zero();
+        if gradient == Vec3::ZERO {
+            break;
+        }
+        point += gradient * (clearance - distance);
+    }
+    point
+}
+
+/// Cell state in the DBM grid
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum DbmCell {
+    Empty,
+    Channel,
+    Target,
+}
+
+/// Potential grid used by [`LightningTree::generate_dbm`]
+struct DbmGrid {
+    dims: [usize; 3],
+    potential: Vec<f32>,
+    state: Vec<DbmCell>,
+}
+
+impl DbmGrid {
+    fn new(dims: [usize; 3]) -> Self {
+        let len = dims[0] * dims[1] * dims[2];
+        let mut grid = Self {
+            dims,
+            potential: vec![0.0; len],
+            state: vec![DbmCell::Empty; len],
+        };
+        // Start from a linear ramp along the axis so relaxation converges quickly
+        for cell in 0..len {
+            grid.potential[cell] = grid.coords(cell)[0] as f32 / (dims[0] - 1).max(1) as f32;
+        }
+        grid
+    }
+
+    fn index(&self, [a, b, c]: [usize; 3]) -> usize {
+        (a * self.dims[1] + b) * self.dims[2] + c
+    }
+
+    fn coords(&self, cell: usize) -> [usize; 3] {
+        let c = cell % self.dims[2];
+        let b = (cell / self.dims[2]) % self.dims[1];
+        let a = cell / (self.dims[1] * self.dims[2]);
+        [a, b, c]
+    }
+
+    fn lattice_distance_squared(&self, a: usize, b: usize) -> usize {
+        let (a, b) = (self.coords(a), self.coords(b));
+        (0..3).map(|i| a[i].abs_diff(b[i]).pow(2)).sum()
+    }
+
+    /// All in-bounds cells touching `cell`, including diagonals
+    fn neighbors(&self, cell: usize) -> impl Iterator<Item = usize> + '_ {
+        let [a, b, c] = self.coords(cell);
+        let dims = self.dims;
+        (0..27).filter_map(move |offset| {
+            let delta = [offset / 9, (offset / 3) % 3, offset % 3];
+            if delta == [1, 1, 1] {
+                return None;
+            }
+            let na = (a + delta[0]).checked_sub(1).filter(|&v| v < dims[0])?;
+            let nb = (b + delta[1]).checked_sub(1).filter(|&v| v < dims[1])?;
+            let nc = (c + delta[2]).checked_sub(1).filter(|&v| v < dims[2])?;
+            Some((na * dims[1] + nb) * dims[2] + nc)
+        })
+    }
+
+    fn push_candidates(&self, cell: usize, candidates: &mut Vec<usize>, is_candidate: &mut [bool]) {
+        for neighbor in self.neighbors(cell) {
+            if self.state[neighbor] != DbmCell::Channel && !is_candidate[neighbor] {
+                is_candidate[neighbor] = true;
+                candidates.push(neighbor);
+            }
+        }
+    }
+
+    /// Gauss-Seidel sweeps over the empty cells (Neumann boundary at the grid edges)
+    fn relax(&mut self, iterations: u32) {
+        let [da, db, dc] = self.dims;
+        for _ in 0..iterations {
+            for a in 0..da {
+                for b in 0..db {
+                    for c in 0..dc {
+                        let cell = (a * db + b) * dc + c;
+                        if self.state[cell] != DbmCell::Empty {
+                            continue;
+                        }
+                        let mut sum = 0.0;
+                        let mut count = 0.0;
+                        let mut add = |other: usize| {
+                            sum += self.potential[other];
+                            count += 1.0;
+                        };
+                        if a > 0 {
+                            add(cell - db * dc);
+                        }
+                        if a + 1 < da {
+                            add(cell + db * dc);
+                        }
+                        if b > 0 {
+                            add(cell - dc);
+                        }
+                        if b + 1 < db {
+                            add(cell + dc);
+                        }
+                        if c > 0 {
+                            add(cell - 1);
+                        }
+                        if c + 1 < dc {
+                            add(cell + 1);
+                        }
+                        self.potential[cell] = sum / count;
+                    }
+                }
+            }
+        }
+    }
+}
+
+/// Timing of the return stroke and re-strikes that follow the leader
+///
+/// Once the leader connects, a bright return stroke lights the whole channel, then
+/// `restrike_count` dimmer strokes travel down the main channel again, each slightly
+/// displaced. The channel is dark between strokes.
+#[derive(Debug, Clone)]
+pub struct StrokeConfig {
+    /// Random seed for stroke timing and re-strike jitter
+    pub seed: u64,
+    /// Number of re-strikes after the return stroke
+    /// Typical: 2 - 5
+    pub restrike_count: u32,
+    /// Seconds the return stroke stays lit
+    pub return_duration: f32,
+    /// Seconds each re-strike stays lit
+    pub restrike_duration: f32,
+    /// Average dark gap in seconds before each re-strike
+    pub interval: f32,
+    /// Each gap is scaled by a random factor in `1 - variation..1 + variation`
+    pub interval_variation: f32,
+    /// Brightness of re-strikes relative to the return stroke (0.0 - 1.0)
+    pub restrike_brightness: f32,
+    /// Sideways jitter of re-strikes as a fraction of the local segment length
+    pub jitter: f32,
+}
+
+impl Default for StrokeConfig {
+    fn default() -> Self {
+        Self {
+            seed: 0,
+            restrike_count: 3,
+            return_duration: 0.08,
+            restrike_duration: 0.04,
+            interval: 0.06,
+            interval_variation: 0.5,
+            restrike_brightness: 0.6,
+            jitter: 0.05,
+        }
+    }
+}
+
+impl StrokeConfig {
+    /// Build the stroke schedule for `tree`, starting when its leader connects
+    ///
+    /// The first stroke is the return stroke; the rest are re-strikes.
+    pub fn schedule(&self, tree: &LightningTree) -> Vec<LightningStroke> {
+        let mut rng = ChaCha8Rng::seed_from_u64(self.seed);
+        let mut start = tree.strike_time();
+        let mut strokes = vec![LightningStroke {
+            start,
+            duration: self.return_duration,
+            brightness: 1.0,
+            offsets: vec![Vec3::ZERO; tree.main_path.len()],
+        }];
+        start += self.return_duration;
+
+        let variation = self.interval_variation.clamp(0.0, 1.0);
+        for _ in 0..self.restrike_count {
+            start += self.interval * sample_range(&mut rng, 1.0 - variation, 1.0 + variation);
+
+            // Jitter inner nodes sideways; the root and strike point stay put
+            let path = &tree.main_path;
+            let offsets = (0..path.len())
+                .map(|i| {
+                    if i == 0 || i + 1 == path.len() {
+                        return Vec3::ZERO;
+                    }
+                    let previous = tree.nodes[path[i - 1]].position;
+                    let next = tree.nodes[path[i + 1]].position;
+                    let local_length = (next - previous).length() * 0.5;
+                    random_perpendicular(next - previous, &mut rng)
+                        * sample_range(&mut rng, -1.0, 1.0)
+                        * self.jitter
+                        * local_length
+                })
+                .collect();
+
+            strokes.push(LightningStroke {
+                start,
+                duration: self.restrike_duration,
+                brightness: self.restrike_brightness,
+                offsets,
+            });
+            start += self.restrike_duration;
+        }
+        strokes
+    }
+}
+
+/// One flash of the channel in a multi-stroke strike
+#[derive(Debug, Clone)]
+pub struct LightningStroke {
+    /// Seconds after the bolt spawns at which the stroke starts
+    pub start: f32,
+    /// Seconds the stroke stays lit
+    pub duration: f32,
+    /// Brightness of the main channel (1.0 = return stroke)
+    pub brightness: f32,
+    /// Offset of each [`LightningTree::main_path`] node during this stroke
+    pub offsets: Vec<Vec3>,
+}
+
+impl LightningStroke {
+    /// Whether the stroke is lit at `time` seconds after the bolt spawned
+    pub fn is_active(&self, time: f32) -> bool {
+        time >= self.start && time < self.start + self.duration
+    }
+}
+
+/// Persistent arc that re-rolls its shape at a fixed rate
+///
+/// For Tesla coils, beam weapons and electrified fences. Created with
+/// [`ProceduralLightning::continuous`]; the tree is regenerated in place and the
+/// entity keeps its particle effects until [`ContinuousArc::stop`] is called and the
+/// current flash runs out.
+pub struct ContinuousArc {
+    /// Generator used for every re-roll
+    pub generator: Box<dyn LightningGenerator>,
+    /// Start of the arc in the entity's local space
+    pub start: Vec3,
+    /// End of the arc in the entity's local space
+    pub end: Vec3,
+    /// Repeating timer that triggers each re-roll
+    pub regen_timer: Timer,
+    /// Number of re-rolls so far; added to the generator's seed so every shape differs
+    pub generation: u64,
+    running: bool,
+}
+
+impl ContinuousArc {
+    /// Create a running arc that regenerates `rate_hz` times per second
+    pub fn new(
+        start: Vec3,
+        end: Vec3,
+        generator: impl LightningGenerator + 'static,
+        rate_hz: f32,
+    ) -> Self {
+        Self {
+            generator: Box::new(generator),
+            start,
+            end,
+            regen_timer: Timer::from_seconds(1.0 / rate_hz.max(0.001), TimerMode::Repeating),
+            generation: 0,
+            running: true,
+        }
+    }
+
+    /// Stop re-rolling; the entity despawns once its current lifetime runs out
+    pub fn stop(&mut self) {
+        self.running = false;
+    }
+
+    /// Whether the arc is still re-rolling
+    pub fn is_running(&self) -> bool {
+        self.running
+    }
+
+    /// Seconds between two re-rolls
+    pub fn period(&self) -> f32 {
+        self.regen_timer.duration().as_secs_f32()
+    }
+
+    /// Re-roll `tree` with the next generation's seed
+    pub fn regenerate(&mut self, tree: &mut LightningTree) {
+        self.generation += 1;
+        let seed = self.generator.seed().wrapping_add(self.generation);
+        let mut rng = ChaCha8Rng::seed_from_u64(seed);
+        self.generator
+            .generate_into(self.start, self.end, &mut rng, tree);
+    }
+}
+
+/// Shimmer that moves existing nodes with coherent noise, without regenerating
+///
+/// Each node is offset by up to `amplitude` times the length of the segment leading
+/// to it. Offsets fade to zero near the root and the strike points, so the arc stays
+/// pinned to its endpoints. Topology and seed are untouched.
+#[derive(Debug, Clone)]
+pub struct LightningCrawl {
+    /// Largest offset as a fraction of the segment length leading to the node
+    /// Typical: 0.05 - 0.3
+    pub amplitude: f32,
+    /// Speed of the noise in cycles per second
+    /// Typical: 2.0 - 15.0
+    pub frequency: f32,
+    time: f32,
+    rest: Vec<Vec3>,
+    reach: Vec<f32>,
+    noise_scale: f32,
+}
+
+impl LightningCrawl {
+    /// Create a crawl with the given amplitude and frequency
+    pub fn new(amplitude: f32, frequency: f32) -> Self {
+        Self {
+            amplitude,
+            frequency,
+            time: 0.0,
+            rest: Vec::new(),
+            reach: Vec::new(),
+            noise_scale: 1.0,
+        }
+    }
+
+    /// Forget the rest pose so it is captured again from the tree's current positions
+    ///
+    /// Needed after the tree was replaced (continuous arcs do this on every re-roll).
+    pub fn reset(&mut self) {
+        self.rest.clear();
+        self.reach.clear();
+    }
+
+    /// Advance the noise by `delta_secs` and move the tree's nodes around their rest pose
+    pub fn apply(&mut self, tree: &mut LightningTree, delta_secs: f32) {
+        if self.rest.len() != tree.nodes.len() {
+            self.capture(tree);
+        }
+        self.time += delta_secs;
+
+        let phase = Vec3::splat(self.time * self.frequency);
+        for (idx, node) in tree.nodes.iter_mut().enumerate() {
+            let sample = self.rest[idx] * self.noise_scale + phase;
+            let offset = Vec3::new(
+                perlin_noise(sample),
+                perlin_noise(sample + Vec3::new(31.4, 0.0, 0.0)),
+                perlin_noise(sample + Vec3::new(0.0, 47.2, 0.0)),
+            );
+            node.position = self.rest[idx] + offset * self.amplitude * self.reach[idx];
+        }
+    }
+
+    /// Move the rest pose along with a warped tree
+    fn warp(&mut self, warp: &AxisWarp) {
+        for position in &mut self.rest {
+            *position = warp.point(*position);
+        }
+    }
+
+    /// Store rest positions and per-node offset reach (segment length times pin falloff)
+    fn capture(&mut self, tree: &LightningTree) {
+        self.rest.clear();
+        self.rest
+            .extend(tree.nodes.iter().map(|node| node.position));
+
+        let pins: Vec<Vec3> = std::iter::once(0)
+            .chain(tree.strike_points.iter().copied())
+            .filter_map(|idx| tree.nodes.get(idx).map(|node| node.position))
+            .collect();
+        let span = tree
+            .strike_points
+            .first()
+            .map_or(0.0, |&idx| tree.nodes[idx].position.distance(tree.root));
+        let falloff = (span * 0.1).max(f32::EPSILON);
+
+        self.reach.clear();
+        self.reach.extend(tree.nodes.iter().map(|node| {
+            let Some(parent) = node.parent else {
+                return 0.0;
+            };
+            let pin_distance = pins
+                .iter()
+                .map(|pin| pin.distance(node.position))
+                .fold(f32::INFINITY, f32::min);
+            node.position.distance(tree.nodes[parent].position)
+                * (pin_distance / falloff).clamp(0.0, 1.0)
+        }));
+
+        // Neighbouring nodes sit a fraction of a noise cell apart, so offsets are coherent
+        let mean_segment = if tree.segments.is_empty() {
+            1.0
+        } else {
+            tree.segments
+                .iter()
+                .map(|&(a, b)| tree.nodes[a].position.distance(tree.nodes[b].position))
+                .sum::<f32>()
+                / tree.segments.len() as f32
+        };
+        self.noise_scale = 1.0 / (4.0 * mean_segment).max(f32::EPSILON);
+    }
+}
+
+/// Binds a bolt endpoint to an entity so the bolt follows it
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct EndpointAnchor {
+    /// Entity the endpoint is attached to (needs a `GlobalTransform`)
+    pub entity: Entity,
+    /// Offset in the entity's local space (e.g. a turret's muzzle)
+    pub offset: Vec3,
+}
+
+impl EndpointAnchor {
+    /// Attach to `entity` at `offset` in its local space
+    pub fn new(entity: Entity, offset: Vec3) -> Self {
+        Self { entity, offset }
+    }
+}
+
+/// Component for a procedural lightning effect entity
+#[derive(Component)]
+pub struct ProceduralLightning {
+    /// The generated lightning tree
+    pub tree: LightningTree,
+    /// Animation timer for flickering/pulsing
+    pub animation_timer: Timer,
+    /// Lifetime timer for despawning
+    pub lifetime: Timer,
+    /// Base color for the lightning
+    pub color: Color,
+    /// Particle effect entities (core, glow, sparks, impact)
+    pub particle_entities: Vec<Entity>,
+    /// Whether to render debug gizmos
+    pub show_gizmos: bool,
+    /// Whether to enable flicker effect (on/off intervals)
+    pub enable_flicker: bool,
+    /// Return stroke and re-strikes played after the leader (empty = one continuous flash)
+    pub strokes: Vec<LightningStroke>,
+    /// Set for persistent arcs that regenerate until stopped
+    pub continuous: Option<ContinuousArc>,
+    /// Per-frame shimmer applied to the tree between regenerations
+    pub crawl: Option<LightningCrawl>,
+    /// Entity the root follows
+    pub source: Option<EndpointAnchor>,
+    /// Entity the primary strike point follows
+    pub target: Option<EndpointAnchor>,
+    /// Subdivision level to draw (`None` = every segment); set by [`LightningLod`]
+    pub detail_level: Option<u32>,
+}
+
+impl ProceduralLightning {
+    /// Create a new procedural lightning effect using any [`LightningGenerator`]
+    pub fn new<G: LightningGenerator + ?Sized>(
+        start: Vec3,
+        end: Vec3,
+        generator: &G,
+        lifetime_secs: f32,
+        color: Color,
+    ) -> Self {
+        let tree = generator.generate_seeded(start, end);
+        Self::from_tree(tree, lifetime_secs, color)
+    }
+
+    /// Create a lightning effect from an already generated tree
+    /// (e.g. a multi-target strike from [`LightningTree::generate_forked`])
+    ///
+    /// The lifetime starts counting once the leader has reached the strike point,
+    /// so a growing bolt stays fully lit for `lifetime_secs`.
+    pub fn from_tree(tree: LightningTree, lifetime_secs: f32, color: Color) -> Self {
+        let lifetime_secs = tree.strike_time() + lifetime_secs;
+        Self {
+            tree,
+            animation_timer: Timer::from_seconds(0.05, TimerMode::Repeating),
+            lifetime: Timer::from_seconds(lifetime_secs, TimerMode::Once),
+            color,
+            particle_entities: Vec::new(), // Will be populated after spawn
+            show_gizmos: false,            // Particles by default
+            enable_flicker: false,         // No flicker by default
+            strokes: Vec::new(),
+            continuous: None,
+            crawl: None,
+            source: None,
+            target: None,
+            detail_level: None,
+        }
+    }
+
+    /// Keep the root attached to `entity` (at `offset` in its local space)
+    #[must_use]
+    pub fn with_source(mut self, entity: Entity, offset: Vec3) -> Self {
+        self.source = Some(EndpointAnchor::new(entity, offset));
+        self
+    }
+
+    /// Keep the primary strike point attached to `entity` (at `offset` in its local space)
+    #[must_use]
+    pub fn with_target(mut self, entity: Entity, offset: Vec3) -> Self {
+        self.target = Some(EndpointAnchor::new(entity, offset));
+        self
+    }
+
+    /// Make the bolt shimmer with coherent noise (see [`LightningCrawl`])
+    #[must_use]
+    pub fn with_crawl(mut self, amplitude: f32, frequency: f32) -> Self {
+        self.crawl = Some(LightningCrawl::new(amplitude, frequency));
+        self
+    }
+
+    /// Create a persistent arc that regenerates its tree `rate_hz` times per second
+    ///
+    /// Call [`ContinuousArc::stop`] on `continuous` to let it expire.
+    pub fn continuous(
+        start: Vec3,
+        end: Vec3,
+        generator: impl LightningGenerator + 'static,
+        rate_hz: f32,
+        color: Color,
+    ) -> Self {
+        let arc = ContinuousArc::new(start, end, generator, rate_hz);
+        let tree = arc.generator.generate_seeded(start, end);
+        let mut lightning = Self::from_tree(tree, arc.period(), color);
+        lightning.continuous = Some(arc);
+        lightning
+    }
+
+    /// Play a return stroke and re-strikes on the main channel once the leader connects
+    ///
+    /// The lifetime is extended if needed so that every stroke is shown.
+    #[must_use]
+    pub fn with_strokes(mut self, config: &StrokeConfig) -> Self {
+        self.strokes = config.schedule(&self.tree);
+        if let Some(last) = self.strokes.last() {
+            let end = last.start + last.duration;
+            if end > self.lifetime.duration().as_secs_f32() {
+                self.lifetime
+                    .set_duration(std::time::Duration::from_secs_f32(end));
+            }
+        }
+        self
+    }
+}
+
+/// Pre-generated unit-space bolts, reused for any strike by retargeting
+///
+/// Each [`LightningConfig`] gets `variants` trees from (0,0,0) to (0,0,1), generated
+/// with seeds `config.seed`, `config.seed + 1`, ... the first time the config is used.
+/// Planar configs whose plane doesn't contain Z (e.g. [`DisplacementPlane::XY`]) run
+/// their variants along an in-plane unit axis instead, so retargeting onto an in-plane
+/// strike keeps the offsets in the plane.
+/// Because variants are generated in unit space, a `target_segment_length` is
+/// measured against a bolt of length 1, and so is a noise `frequency` (pick a few
+/// cycles per unit). Noise variants are told apart by shifting `noise.offset`.
+#[derive(Resource)]
+pub struct LightningCache {
+    variants: usize,
+    entries: HashMap<LightningConfig, Vec<LightningTree>>,
+}
+
+impl Default for LightningCache {
+    fn default() -> Self {
+        Self::new(8)
+    }
+}
+
+impl LightningCache {
+    /// Create an empty cache holding `variants` trees per config
+    pub fn new(variants: usize) -> Self {
+        Self {
+            variants: variants.max(1),
+            entries: HashMap::new(),
+        }
+    }
+
+    /// The unit-space variants for `config`, generating them if needed
+    ///
+    /// Variants are left unpruned, since `prune.min_length` is a world-space length;
+    /// [`LightningCache::tree`] prunes and re-times them after retargeting. Configs that
+    /// only differ in `growth` or `prune` therefore share their variants.
+    pub fn variants(&mut self, config: &LightningConfig) -> &[LightningTree] {
+        let count = self.variants;
+        let key = Self::cache_key(config);
+        self.entries.entry(key.clone()).or_insert_with(|| {
+            (0..count as u64)
+                .map(|offset| {
+                    // The noise field ignores the seed, so each variant moves through it
+                    let noise = key.noise.clone().map(|noise| NoiseDisplacement {
+                        offset: noise.offset + NOISE_VARIANT_SHIFT * offset as f32,
+                        ..noise
+                    });
+                    let variant = LightningConfig {
+                        seed: key.seed.wrapping_add(offset),
+                        noise,
+                        ..key.clone()
+                    };
+                    LightningTree::generate(Vec3::ZERO, Self::unit_axis(&key), &variant)
+                })
+                .collect()
+        })
+    }
+
+    /// Variant `index` (wrapped to the variant count) mapped onto `start -> end`
+    ///
+    /// The tree is pruned and its arrival times are recomputed at the new length, so
+    /// both match a bolt generated directly between the endpoints.
+    pub fn tree(
+        &mut self,
+        config: &LightningConfig,
+        index: usize,
+        start: Vec3,
+        end: Vec3,
+    ) -> LightningTree {
+        let variants = self.variants(config);
+        let index = index % variants.len();
+        let mut tree = variants[index].retargeted(start, end);
+        tree.prune_for(config);
+        tree.assign_arrival_times(&config.growth, config.seed.wrapping_add(index as u64));
+        tree
+    }
+
+    /// Drop every cached variant
+    pub fn clear(&mut self) {
+        self.entries.clear();
+    }
+
+    /// `config` with the fields that are only applied after retargeting reset
+    fn cache_key(config: &LightningConfig) -> LightningConfig {
+        LightningConfig {
+            growth: GrowthConfig::default(),
+            prune: PruneConfig::default(),
+            ..config.clone()
+        }
+    }
+
+    /// End point of the unit-space variants for `config`
+    fn unit_axis(config: &LightningConfig) -> Vec3 {
+        let plane = config.displacement_plane;
+        match config.displacement_mode {
+            DisplacementMode::Planar if plane.normal().z.abs() > 1e-4 => plane.fallback_axis(),
+            _ => Vec3::Z,
+        }
+    }
+}
+
+/// Distance-based level of detail for [`ProceduralLightning`] bolts
+///
+/// Insert this resource to enable LOD. Bolts closer to the active camera than
+/// `full_detail_distance` draw every segment; each doubling of the distance beyond
+/// that drops one subdivision level, down to `min_level`.
+#[derive(Resource, Debug, Clone)]
+pub struct LightningLod {
+    /// Distance up to which bolts are drawn at full detail
+    pub full_detail_distance: f32,
+    /// Coarsest subdivision level drawn, however far away the bolt is
+    pub min_level: u32,
+}
+
+impl Default for LightningLod {
+    fn default() -> Self {
+        Self {
+            full_detail_distance: 50.0,
+            min_level: 2,
+        }
+    }
+}
+
+impl LightningLod {
+    /// Detail level for a bolt `distance` away whose finest level is `max_level`
+    pub fn level_for_distance(&self, distance: f32, max_level: u32) -> u32 {
+        if distance <= self.full_detail_distance {
+            return max_level;
+        }
+        let dropped = (distance / self.full_detail_distance.max(f32::EPSILON))
+            .log2()
+            .ceil() as u32;
+        max_level
+            .saturating_sub(dropped)
+            .max(self.min_level.min(max_level))
+    }
+}
+
+/// Plugin for procedural lightning system
+///
+/// Bolts are updated and drawn in `PostUpdate`, after transform propagation, so tracked
+/// endpoints follow their anchors' positions from the same frame.
+pub struct ProceduralLightningPlugin;
+
+impl Plugin for ProceduralLightningPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<LightningCache>().add_systems(
+            PostUpdate,
+            (
+                track_lightning_endpoints,
+                regenerate_continuous_arcs,
+                crawl_lightning,
+                select_lightning_detail,
+                update_procedural_lightning,
+                cleanup_expired_lightning,
+            )
+                .chain()
+                .after(TransformSystems::Propagate),
+        );
+    }
+}
+
+/// Particle emitter entities moved by [`track_lightning_endpoints`]
+type ParticleEmitters<'w, 's> = Query<
+    'w,
+    's,
+    (
+        &'static mut Transform,
+        &'static mut GlobalTransform,
+        &'static mut EffectProperties,
+    ),
+    Without<ProceduralLightning>,
+>;
+
+/// Warp bolts with anchored endpoints so they stay attached to moving entities
+///
+/// The tree is rotated and stretched along its start -> end axis; sideways offsets
+/// keep their size, so the shape is preserved. Particle emitters are moved to the new
+/// start and aimed at the new end. Anchors that no longer exist leave their endpoint
+/// where it was.
+fn track_lightning_endpoints(
+    mut query: Query<(&mut ProceduralLightning, &Transform)>,
+    mut transforms: ParamSet<(Query<&GlobalTransform>, ParticleEmitters)>,
+) {
+    for (mut lightning, transform) in &mut query {
+        if lightning.source.is_none() && lightning.target.is_none() {
+            continue;
+        }
+        let lightning = &mut *lightning;
+        let Some(&end_idx) = lightning.tree.strike_points.first() else {
+            continue;
+        };
+
+        // Anchor positions are in world space; the tree lives in the entity's local space
+        let to_local = transform.compute_affine().inverse();
+        let anchors = transforms.p0();
+        let anchor_position = |anchor: Option<EndpointAnchor>| {
+            let anchor = anchor?;
+            let global = anchors.get(anchor.entity).ok()?;
+            Some(to_local.transform_point3(global.transform_point(anchor.offset)))
+        };
+
+        let old_start = lightning.tree.nodes[0].position;
+        let old_end = lightning.tree.nodes[end_idx].position;
+        let new_start = anchor_position(lightning.source).unwrap_or(old_start);
+        let new_end = anchor_position(lightning.target).unwrap_or(old_end);
+        if new_start == old_start && new_end == old_end {
+            continue;
+        }
+
+        let warp = AxisWarp::new(old_start, old_end, new_start, new_end, 1.0);
+        lightning.tree.warp(&warp);
+        if let Some(crawl) = lightning.crawl.as_mut() {
+            crawl.warp(&warp);
+        }
+        for stroke in &mut lightning.strokes {
+            for offset in &mut stroke.offsets {
+                *offset = warp.rotation * *offset;
+            }
+        }
+        // Re-rolls of continuous arcs start from the tracked endpoints
+        if let Some(arc) = lightning.continuous.as_mut() {
+            arc.start = new_start;
+            arc.end = new_end;
+        }
+
+        // Particles are simulated in world space. Transforms have already been
+        // propagated this frame, so the emitter's global transform is set directly.
+        let emitter = transform.transform_point(new_start);
+        let direction = (transform.rotation * (new_end - new_start)).normalize_or_zero();
+        let mut particles = transforms.p1();
+        for &particle_entity in &lightning.particle_entities {
+            if let Ok((mut particle_transform, mut global, mut properties)) =
+                particles.get_mut(particle_entity)
+            {
+                particle_transform.translation = emitter;
+                *global = GlobalTransform::from(*particle_transform);
+                properties.set(PARTICLE_DIRECTION_PROPERTY, direction.into());
+            }
+        }
+    }
+}
+
+/// Re-roll running continuous arcs in place and restart their lifetime
+#[allow(clippy::needless_pass_by_value)]
+fn regenerate_continuous_arcs(mut query: Query<&mut ProceduralLightning>, time: Res<Time>) {
+    for mut lightning in &mut query {
+        let lightning = &mut *lightning;
+        let Some(arc) = lightning.continuous.as_mut() else {
+            continue;
+        };
+        if !arc.is_running() {
+            continue;
+        }
+
+        arc.regen_timer.tick(time.delta());
+        if arc.regen_timer.just_finished() {
+            arc.regenerate(&mut lightning.tree);
+            // Stroke schedules and crawl rest poses are built for one tree
+            lightning.strokes.clear();
+            if let Some(crawl) = lightning.crawl.as_mut() {
+                crawl.reset();
+            }
+            let lifetime = lightning.tree.strike_time() + arc.period();
+            lightning
+                .lifetime
+                .set_duration(std::time::Duration::from_secs_f32(lifetime));
+            lightning.lifetime.reset();
+        }
+    }
+}
+
+/// Shimmer lightning trees that have a crawl
+#[allow(clippy::needless_pass_by_value)]
+fn crawl_lightning(mut query: Query<&mut ProceduralLightning>, time: Res<Time>) {
+    for mut lightning in &mut query {
+        let lightning = &mut *lightning;
+        if let Some(crawl) = lightning.crawl.as_mut() {
+            crawl.apply(&mut lightning.tree, time.delta_secs());
+        }
+    }
+}
+
+/// Pick each bolt's detail level from its distance to the active camera
+#[allow(clippy::needless_pass_by_value)]
+fn select_lightning_detail(
+    lod: Option<Res<LightningLod>>,
+    cameras: Query<(&Camera, &GlobalTransform)>,
+    mut query: Query<(&mut ProceduralLightning, &Transform)>,
+) {
+    let Some(lod) = lod else {
+        return;
+    };
+    let Some((_, camera)) = cameras.iter().find(|(camera, _)| camera.is_active) else {
+        return;
+    };
+
+    for (mut lightning, transform) in &mut query {
+        let tree = &lightning.tree;
+        let Some(&end_idx) = tree.strike_points.first() else {
+            continue;
+        };
+        let center = transform
+            .transform_point((tree.nodes[0].position + tree.nodes[end_idx].position) * 0.5);
+        let distance = camera.translation().distance(center);
+        let level = lod.level_for_distance(distance, tree.max_level());
+        lightning.detail_level = Some(level);
+    }
+}
+
+/// Update procedural lightning animations and rendering
+#[allow(clippy::needless_pass_by_value)]
+fn update_procedural_lightning(
+    mut query: Query<(&mut ProceduralLightning, &Transform)>,
+    time: Res<Time>,
+    mut gizmos: Gizmos,
+) {
+    for (mut lightning, transform) in &mut query {
+        lightning.animation_timer.tick(time.delta());
+        lightning.lifetime.tick(time.delta());
+
+        // Draw lightning using gizmos
+        // With flicker: alternates on/off based on timer progress
+        // Without flicker: draw every frame
+        let should_draw = if lightning.enable_flicker {
+            // Flicker on/off - visible for first half of timer cycle
+            lightning.animation_timer.elapsed_secs() / lightning.animation_timer.duration().as_secs_f32() < 0.5
+        } else {
+            true
+        };
+        
+        if should_draw {
+            // Until the leader connects, only reached segments are drawn, dimmed
+            let elapsed = lightning.lifetime.elapsed_secs();
+            let level = lightning.detail_level.unwrap_or(u32::MAX);
+            let leader_phase = elapsed < lightning.tree.strike_time();
+
+            // With strokes, the channel is dark between flashes and re-strikes skip the branches
+            let stroke = if leader_phase || lightning.strokes.is_empty() {
+                None
+            } else {
+                match lightning.strokes.iter().position(|stroke| stroke.is_active(elapsed)) {
+                    Some(index) => Some(index),
+                    None => continue,
+                }
+            };
+            if let Some(index) = stroke {
+                let stroke = &lightning.strokes[index];
+                let points = lightning
+                    .tree
+                    .main_path
+                    .iter()
+                    .zip(&stroke.offsets)
+                    .filter(|(&idx, _)| lightning.tree.nodes[idx].level <= level)
+                    .map(|(&idx, &offset)| {
+                        transform.transform_point(lightning.tree.nodes[idx].position + offset)
+                    });
+                gizmos.linestrip(points, lightning.color.with_alpha(stroke.brightness));
+                if index > 0 {
+                    continue;
+                }
+            }
+
+            for (start_idx, end_idx) in lightning.tree.detail_segments(level) {
+                if leader_phase && lightning.tree.nodes[end_idx].arrival_time > elapsed {
+                    continue;
+                }
+                let start = transform.transform_point(lightning.tree.nodes[start_idx].position);
+                let end = transform.transform_point(lightning.tree.nodes[end_idx].position);
+                let energy = lightning.tree.nodes[start_idx].energy;
+
+                // Vary color intensity by energy
+                let alpha = if lightning.show_gizmos { 0.9 } else { 0.7 };
+                let alpha = if leader_phase { alpha * 0.4 } else { alpha };
+                let color = lightning.color.with_alpha(energy * alpha);
+                gizmos.line(start, end, color);
+            }
+        }
+    }
+}
+
+/// Cleanup expired lightning effects
+#[allow(clippy::needless_pass_by_value)]
+fn cleanup_expired_lightning(mut commands: Commands, query: Query<(Entity, &ProceduralLightning)>) {
+    for (entity, lightning) in &query {
+        let running = lightning
+            .continuous
+            .as_ref()
+            .is_some_and(ContinuousArc::is_running);
+        if lightning.lifetime.is_finished() && !running {
+            // Despawn all particle entities first
+            for &particle_entity in &lightning.particle_entities {
+                if let Ok(mut entity_commands) = commands.get_entity(particle_entity) {
+                    entity_commands.despawn();
+                }
+            }
+            // Then despawn the main lightning entity
+            commands.entity(entity).despawn();
+        }
+    }
+}
+
+/// Helper function to spawn a procedural lightning effect with particles
+pub fn spawn_procedural_lightning<G: LightningGenerator + ?Sized>(
+    commands: &mut Commands,
+    effects: &mut ResMut<Assets<EffectAsset>>,
+    start: Vec3,
+    end: Vec3,
+    generator: &G,
+    lifetime_secs: f32,
+    color: Color,
+    show_gizmos: bool,
+    show_particles: bool,
+) -> Entity {
+    let mut lightning = ProceduralLightning::new(start, end, generator, lifetime_secs, color);
+    lightning.show_gizmos = show_gizmos;
+    spawn_lightning(commands, effects, lightning, show_particles)
+}
+
+/// Spawn an already built lightning effect (e.g. a [`ProceduralLightning::continuous`] arc),
+/// optionally with particles
+pub fn spawn_lightning(
+    commands: &mut Commands,
+    effects: &mut ResMut<Assets<EffectAsset>>,
+    mut lightning: ProceduralLightning,
+    show_particles: bool,
+) -> Entity {
+    // Create multi-layered particle effects if enabled
+    let particle_entities = if show_particles {
+        create_procedural_lightning_particle_effects(
+            commands,
+            effects,
+            &lightning.tree,
+            lightning.color,
+        )
+    } else {
+        Vec::new()
+    };
+    lightning.particle_entities = particle_entities;
+
+    commands.spawn((lightning, Transform::default())).id()
+}
+
+/// Marker for entities that chain lightning may hop to (the default candidate filter)
+#[derive(Component, Default)]
+pub struct ChainLightningTarget;
+
+/// Chain lightning that hops from a source entity to nearby candidates in sequence
+///
+/// Each hop spawns a [`ProceduralLightning`] bolt from the previously hit entity to the
+/// closest candidate within `hop_range` that has not been hit yet, and writes a
+/// [`ChainLightningHit`] message. Candidates are entities with a `GlobalTransform`
+/// matching the query filter `F`. The component removes itself (leaving the entity it is
+/// on alive, e.g. the caster) once `max_hops` is reached or no candidate is in range.
+/// Requires [`ChainLightningPlugin`] for the same `F`.
+#[derive(Component)]
+pub struct ChainLightning<F: QueryFilter + 'static = With<ChainLightningTarget>> {
+    /// Entity the first hop starts from (never hit by the chain)
+    pub source: Entity,
+    /// Maximum distance covered by a single hop
+    pub hop_range: f32,
+    /// Maximum number of entities hit
+    pub max_hops: u32,
+    /// Delay between consecutive hops
+    pub hop_timer: Timer,
+    /// Generation parameters for each hop's bolt (the seed is offset per hop)
+    pub config: LightningConfig,
+    /// Lifetime of each hop's bolt in seconds
+    pub bolt_lifetime: f32,
+    /// Base color for the bolts
+    pub color: Color,
+    /// Whether bolts render debug gizmos
+    pub show_gizmos: bool,
+    /// Whether bolts spawn particle effects
+    pub show_particles: bool,
+    /// Entities hit so far, in order
+    pub hits: Vec<Entity>,
+    /// Last known position of the entity the next hop starts from
+    last_position: Option<Vec3>,
+    filter: PhantomData<fn() -> F>,
+}
+
+impl<F: QueryFilter + 'static> ChainLightning<F> {
+    /// Create a chain lightning starting at `source`
+    pub fn new(
+        source: Entity,
+        hop_range: f32,
+        max_hops: u32,
+        hop_delay_secs: f32,
+        config: LightningConfig,
+        color: Color,
+    ) -> Self {
+        Self {
+            source,
+            hop_range,
+            max_hops,
+            hop_timer: Timer::from_seconds(hop_delay_secs, TimerMode::Repeating),
+            config,
+            bolt_lifetime: 0.3,
+            color,
+            show_gizmos: true,
+            show_particles: false,
+            hits: Vec::new(),
+            last_position: None,
+            filter: PhantomData,
+        }
+    }
+}
+
+/// Message written every time a [`ChainLightning`] hits an entity
+#[derive(Message, Debug, Clone)]
+pub struct ChainLightningHit {
+    /// The chain lightning entity
+    pub chain: Entity,
+    /// Entity the hop started from (the chain source for the first hop)
+    pub from: Entity,
+    /// Entity that was hit
+    pub target: Entity,
+    /// Hop number, starting at 0
+    pub hop: u32,
+    /// The [`ProceduralLightning`] bolt spawned for this hop
+    pub bolt: Entity,
+}
+
+/// Plugin driving [`ChainLightning`] components whose candidates match the filter `F`
+///
+/// Add [`ProceduralLightningPlugin`] as well so the spawned bolts render and expire.
+pub struct ChainLightningPlugin<F: QueryFilter + 'static = With<ChainLightningTarget>>(
+    PhantomData<fn() -> F>,
+);
+
+impl<F: QueryFilter + 'static> Default for ChainLightningPlugin<F> {
+    fn default() -> Self {
+        Self(PhantomData)
+    }
+}
+
+impl<F: QueryFilter + 'static> Plugin for ChainLightningPlugin<F> {
+    fn build(&self, app: &mut App) {
+        app.add_message::<ChainLightningHit>()
+            .add_systems(Update, advance_chain_lightning::<F>);
+    }
+}
+
+/// Perform due hops for every chain lightning
+#[allow(clippy::needless_pass_by_value)]
+fn advance_chain_lightning<F: QueryFilter + 'static>(
+    mut commands: Commands,
+    mut effects: ResMut<Assets<EffectAsset>>,
+    time: Res<Time>,
+    mut chains: Query<(Entity, &mut ChainLightning<F>)>,
+    candidates: Query<(Entity, &GlobalTransform), F>,
+    positions: Query<&GlobalTransform>,
+    mut hit_messages: MessageWriter<ChainLightningHit>,
+) {
+    for (chain_entity, mut chain) in &mut chains {
+        chain.hop_timer.tick(time.delta());
+        let hop = chain.hits.len() as u32;
+        if hop > 0 && !chain.hop_timer.just_finished() {
+            continue;
+        }
+
+        let from = chain.hits.last().copied().unwrap_or(chain.source);
+        let origin = positions
+            .get(from)
+            .map(GlobalTransform::translation)
+            .ok()
+            .or(chain.last_position);
+        let next = origin.filter(|_| hop < chain.max_hops).and_then(|origin| {
+            let available = candidates
+                .iter()
+                .filter(|(entity, _)| *entity != chain.source && !chain.hits.contains(entity))
+                .map(|(entity, transform)| (entity, transform.translation()));
+            nearest_chain_target(origin, chain.hop_range, available).map(|next| (origin, next))
+        });
+
+        let Some((origin, (target, target_position))) = next else {
+            commands.entity(chain_entity).remove::<ChainLightning<F>>();
+            continue;
+        };
+
+        let config = LightningConfig {
+            seed: chain.config.seed.wrapping_add(u64::from(hop)),
+            ..chain.config.clone()
+        };
+        let bolt = spawn_procedural_lightning(
+            &mut commands,
+            &mut effects,
+            origin,
+            target_position,
+            &config,
+            chain.bolt_lifetime,
+            chain.color,
+            chain.show_gizmos,
+            chain.show_particles,
+        );
+
+        hit_messages.write(ChainLightningHit {
+            chain: chain_entity,
+            from,
+            target,
+            hop,
+            bolt,
+        });
+        chain.hits.push(target);
+        chain.last_position = Some(target_position);
+    }
+}
+
+/// Closest candidate within `range` of `origin`
+fn nearest_chain_target(
+    origin: Vec3,
+    range: f32,
+    candidates: impl Iterator<Item = (Entity, Vec3)>,
+) -> Option<(Entity, Vec3)> {
+    candidates
+        .map(|(entity, position)| (entity, position, origin.distance_squared(position)))
+        .filter(|(_, _, distance_squared)| *distance_squared <= range * range)
+        .min_by(|(_, _, a), (_, _, b)| a.total_cmp(b))
+        .map(|(entity, position, _)| (entity, position))
+}
+
+/// Effect property holding the world-space direction particles travel in
+const PARTICLE_DIRECTION_PROPERTY: &str = "direction";
+
+/// Create traveling ionized particle effect for procedural lightning
+///
+/// Returns vector of entity IDs for particle effects
+///
+/// Creates particles that travel from spawn point to target with random scatter
+fn create_procedural_lightning_particle_effects(
+    commands: &mut Commands,
+    effects: &mut ResMut<Assets<EffectAsset>>,
+    tree: &LightningTree,
+    color: Color,
+) -> Vec<Entity> {
+    let mut particle_entities = Vec::new();
+
+    // Extract color components
+    let [r, g, b, _] = color.to_srgba().to_f32_array();
+    let base_color = Vec4::new(r, g, b, 1.0);
+
+    let start_pos = tree.nodes[0].position;
+    let end_pos = tree
+        .strike_points
+        .first()
+        .map_or(start_pos, |&idx| tree.nodes[idx].position);
+    
+    // Calculate direction and distance for traveling particles
+    let direction = (end_pos - start_pos).normalize_or_zero();
+    let distance = start_pos.distance(end_pos);
+    
+    // Calculate appropriate lifetime based on distance (particles should reach target)
+    let base_speed = 20.0; // units per second
+    let particle_lifetime = (distance / base_speed).max(0.3);
+
+    // ==== Traveling Ionized Particles ====
+    // Creates particles that travel from spawn to target with random scatter
+    let writer = ExprWriter::new();
+    
+    let intensity = 12.0;
+    let lifetime = writer.lit(particle_lifetime * 0.8).uniform(writer.lit(particle_lifetime * 1.2)).expr();
+    let age = writer.lit(0.0).expr();
+    
+    // Spawn at center with small radius for scatter
+    let spawn_center = writer.lit(Vec3::ZERO).expr();
+    let spawn_radius = writer.lit(0.5).expr();
+    
+    // Velocity pointing from start to end with random variance; the direction is a
+    // property so tracked bolts can turn the stream
+    let direction_property = writer.add_property(PARTICLE_DIRECTION_PROPERTY, direction.into());
+    let speed = writer.lit(base_speed * 0.8).uniform(writer.lit(base_speed * 1.2));
+    let velocity_vec = (writer.prop(direction_property) * speed).expr();
+    
+    let drag = writer.lit(1.5).expr();
+
+    let traveling_effect = EffectAsset::new(
+        512, 
+        SpawnerSettings::rate(200.0.into()), 
+        writer.finish()
+    )
+    .with_name("ionized_particles")
+    .init(SetAttributeModifier::new(Attribute::LIFETIME, lifetime))
+    .init(SetAttributeModifier::new(Attribute::AGE, age))
+    .init(SetPositionSphereModifier {
+        center: spawn_center,
+        radius: spawn_radius,
+        dimension: ShapeDimension::Volume,
+    })
+    .init(SetAttributeModifier::new(Attribute::VELOCITY, velocity_vec))
+    .update(LinearDragModifier::new(drag))
+    .render(ColorOverLifetimeModifier::new({
+        let mut gradient = bevy_hanabi::Gradient::new();
+        gradient.add_key(0.0, base_color * intensity);
+        gradient.add_key(0.4, base_color * intensity * 0.8);
+        gradient.add_key(0.8, base_color * intensity * 0.3);
+        gradient.add_key(1.0, Vec4::ZERO);
+        gradient
+    }))
+    .render(SizeOverLifetimeModifier {
+        gradient: {
+            let mut gradient = bevy_hanabi::Gradient::new();
+            gradient.add_key(0.0, Vec3::splat(0.4));
+            gradient.add_key(0.2, Vec3::splat(0.6));
+            gradient.add_key(0.7, Vec3::splat(0.3));
+            gradient.add_key(1.0, Vec3::splat(0.1));
+            gradient
+        },
+        screen_space_size: false,
+    });
+
+    let traveling_handle = effects.add(traveling_effect);
+    let traveling_entity = commands
+        .spawn((
+            ParticleEffect::new(traveling_handle),
+            EffectProperties::default(),
+            Transform::from_translation(start_pos),
+        ))
+        .id();
+    particle_entities.push(traveling_entity);
+
+    particle_entities
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_lightning_generation() {
+        let config = LightningConfig::default();
+        let start = Vec3::new(0.0, 0.0, 0.0);
+        let end = Vec3::new(0.0, 0.0, 100.0);
+
+        let tree = LightningTree::generate(start, end, &config);
+
+        assert!(!tree.nodes.is_empty(), "Should generate nodes");
+        assert!(!tree.segments.is_empty(), "Should generate segments");
+        assert_eq!(tree.nodes[0].position, start, "Start node should match");
+    }
+
+    #[test]
+    fn test_deterministic_generation() {
+        let config = LightningConfig {
+            seed: 42,
+            ..default()
+        };
+        let start = Vec3::ZERO;
+        let end = Vec3::new(0.0, 0.0, 100.0);
+
+        let tree1 = LightningTree::generate(start, end, &config);
+        let tree2 = LightningTree::generate(start, end, &config);
+
+        assert_eq!(
+            tree1.nodes.len(),
+            tree2.nodes.len(),
+            "Same seed should produce same result"
+        );
+        assert_eq!(tree1.segments.len(), tree2.segments.len());
+    }
+
+    #[test]
+    fn test_volumetric_displacement() {
+        let start = Vec3::ZERO;
+        let end = Vec3::new(0.0, 0.0, 100.0);
+
+        let planar = LightningTree::generate(start, end, &LightningConfig::default());
+        assert!(
+            planar.nodes.iter().all(|n| n.position.y == 0.0),
+            "Planar bolts should stay in the XZ plane"
+        );
+
+        let config = LightningConfig {
+            displacement_mode: DisplacementMode::Volumetric,
+            ..default()
+        };
+        let volumetric = LightningTree::generate(start, end, &config);
+        assert!(
+            volumetric.nodes.iter().any(|n| n.position.y.abs() > 0.01),
+            "Volumetric bolts should leave the XZ plane"
+        );
+    }
+
+    #[test]
+    fn test_side_view_plane() {
+        let config = LightningConfig {
+            displacement_plane: DisplacementPlane::XY,
+            ..default()
+        };
+        let tree = LightningTree::generate(Vec3::ZERO, Vec3::new(100.0, 0.0, 0.0), &config);
+
+        assert!(
+            tree.nodes.iter().all(|n| n.position.z == 0.0),
+            "XY bolts should stay in the XY plane"
+        );
+        assert!(tree.nodes.iter().any(|n| n.position.y.abs() > 0.01));
+
+        // The shape doesn't depend on the bolt's size
+        let tiny = LightningTree::generate(Vec3::ZERO, Vec3::new(0.01, 0.0, 0.0), &config);
+        for (node, scaled) in tree.nodes.iter().zip(&tiny.nodes) {
+            assert!(node.position.distance(scaled.position * 10_000.0) < 1e-2);
+        }
+    }
+
+    #[test]
+    fn test_dbm_generation() {
+        let start = Vec3::new(0.0, 100.0, 0.0);
+        let end = Vec3::ZERO;
+        let config = DbmConfig {
+            seed: 7,
+            resolution: 24,
+            ..default()
+        };
+
+        let tree = LightningTree::generate_dbm(start, end, &config);
+
+        assert_eq!(tree.nodes[0].position, start, "Start node should match");
+        assert!(
+            tree.nodes.iter().any(|n| n.position == end),
+            "DBM channel should reach the target"
+        );
+        assert_eq!(
+            tree.segments.len(),
+            tree.nodes.len() - 1,
+            "DBM output is a tree"
+        );
+        for node in &tree.nodes {
+            assert!(node.energy >= 0.0 && node.energy <= 1.0);
+        }
+
+        let again = LightningTree::generate_dbm(start, end, &config);
+        assert_eq!(
+            tree.nodes.len(),
+            again.nodes.len(),
+            "Same seed should match"
+        );
+    }
+
+    #[test]
+    fn test_custom_generator() {
+        struct Straight;
+
+        impl LightningGenerator for Straight {
+            fn generate(&self, start: Vec3, end: Vec3, _rng: &mut dyn RngCore) -> LightningTree {
+                LightningTree::new(
+                    start,
+                    vec![
+                        LightningNode::new(start, 0, 1.0),
+                        LightningNode::new(end, 0, 0.8),
+                    ],
+                    vec![(0, 1)],
+                )
+            }
+        }
+
+        let lightning = ProceduralLightning::new(Vec3::ZERO, Vec3::X, &Straight, 0.5, Color::WHITE);
+        assert_eq!(lightning.tree.segment_count(), 1);
+
+        // Generators can also be used behind a trait object
+        let generators: [&dyn LightningGenerator; 2] =
+            [&LightningConfig::default(), &DbmConfig::default()];
+        for generator in generators {
+            let tree = generator.generate_seeded(Vec3::ZERO, Vec3::new(0.0, 0.0, 50.0));
+            assert!(!tree.segments.is_empty());
+        }
+    }
+
+    #[test]
+    fn test_obstacle_avoidance() {
+        // Sphere of radius 10 sitting right on the straight path
+        let sphere = |point: Vec3| point.length() - 10.0;
+        let generator = AvoidObstacles {
+            config: LightningConfig {
+                seed: 3,
+                ..default()
+            },
+            obstacles: sphere,
+            clearance: 1.0,
+        };
+
+        let tree = generator.generate_seeded(Vec3::new(0.0, 0.0, -50.0), Vec3::new(0.0, 0.0, 50.0));
+
+        assert!(!tree.segments.is_empty());
+        for node in &tree.nodes {
+            assert!(
+                sphere(node.position) >= 0.0,
+                "Nodes should stay outside geometry"
+            );
+        }
+    }
+
+    #[test]
+    fn test_length_driven_subdivision() {
+        let config = LightningConfig {
+            max_depth: 16,
+            target_segment_length: Some(5.0),
+            ..default()
+        };
+
+        let short = LightningTree::generate(Vec3::ZERO, Vec3::new(0.0, 0.0, 10.0), &config);
+        let long = LightningTree::generate(Vec3::ZERO, Vec3::new(0.0, 0.0, 1000.0), &config);
+
+        assert!(short.nodes.len() < 20, "Short zaps should not waste nodes");
+        assert!(long.nodes.len() > short.nodes.len() * 10);
+        for tree in [&short, &long] {
+            for (start_idx, end_idx) in &tree.segments {
+                let length = tree.nodes[*start_idx]
+                    .position
+                    .distance(tree.nodes[*end_idx].position);
+                assert!(length <= 5.0, "Segments should respect the target length");
+            }
+        }
+    }
+
+    #[test]
+    fn test_tree_topology() {
+        let config = LightningConfig {
+            seed: 11,
+            gamma: 0.6,
+            ..default()
+        };
+        let end = Vec3::new(0.0, 0.0, 100.0);
+        let tree = LightningTree::generate(Vec3::ZERO, end, &config);
+
+        assert_eq!(
+            tree.main_path.first(),
+            Some(&0),
+            "Main path starts at the root"
+        );
+        let last = *tree.main_path.last().unwrap();
+        assert_eq!(
+            tree.nodes[last].position, end,
+            "Main path ends at the target"
+        );
+        assert!(tree
+            .main_path
+            .iter()
+            .all(|&idx| tree.nodes[idx].branch_id == 0));
+        assert_eq!(tree.branch_nodes(0), tree.main_path);
+
+        for (idx, node) in tree.nodes.iter().enumerate().skip(1) {
+            let parent = node.parent.expect("Every non-root node has a parent");
+            assert!(tree.nodes[parent].children.contains(&idx));
+        }
+        assert!(tree.branch_count() > 1, "Expected at least one side branch");
+        for branch_id in 1..tree.branch_count() {
+            let branch = tree.branch_nodes(branch_id);
+            let tip = *branch.last().unwrap();
+            assert!(tree.nodes[tip]
+                .children
+                .iter()
+                .all(|&c| tree.nodes[c].branch_id != branch_id));
+        }
+        assert!(tree.tips().count() >= tree.branch_count());
+    }
+
+    #[test]
+    fn test_branch_and_energy_config() {
+        let end = Vec3::new(0.0, 0.0, 100.0);
+        let default_tree = LightningTree::generate(Vec3::ZERO, end, &LightningConfig::default());
+        let explicit = LightningConfig {
+            branch: BranchConfig {
+                length_factor: 0.5,
+                perpendicular_spread: 0.6,
+                forward_min: 0.3,
+                forward_max: 0.8,
+                fork_fraction: 0.5,
+            },
+            energy: EnergyConfig {
+                end_energy: 0.8,
+                branch_factor: 0.5,
+                model: EnergyModel::Interpolated,
+            },
+            ..default()
+        };
+        let explicit_tree = LightningTree::generate(Vec3::ZERO, end, &explicit);
+        assert_eq!(
+            default_tree.get_line_positions(),
+            explicit_tree.get_line_positions(),
+            "Defaults should match the previous hard-coded values"
+        );
+
+        // Degenerate ranges must not panic
+        let narrow = LightningConfig {
+            gamma: 1.0,
+            branch: BranchConfig {
+                perpendicular_spread: 0.0,
+                forward_min: 0.5,
+                forward_max: 0.5,
+                ..default()
+            },
+            energy: EnergyConfig {
+                end_energy: 0.2,
+                branch_factor: 0.9,
+                ..default()
+            },
+            ..default()
+        };
+        let tree = LightningTree::generate(Vec3::ZERO, end, &narrow);
+        assert_eq!(
+            tree.nodes[1].energy, 0.2,
+            "End node uses the configured energy"
+        );
+
+        // Without any spread or forward component, branches still get a direction
+        let collapsed = LightningConfig {
+            gamma: 1.0,
+            branch: BranchConfig {
+                perpendicular_spread: 0.0,
+                forward_min: 0.0,
+                forward_max: 0.0,
+                ..default()
+            },
+            ..default()
+        };
+        let tree = LightningTree::generate(Vec3::ZERO, end, &collapsed);
+        assert!(tree.branch_count() > 1);
+        assert!(tree.nodes.iter().all(|node| node.position.is_finite()));
+        assert!(tree.nodes.iter().all(|node| node.energy.is_finite()));
+    }
+
+    #[test]
+    fn test_forked_strike() {
+        let start = Vec3::new(0.0, 200.0, 0.0);
+        let targets = [
+            Vec3::new(-60.0, 0.0, 0.0),
+            Vec3::new(0.0, 0.0, 40.0),
+            Vec3::new(50.0, 0.0, -20.0),
+        ];
+        let tree = LightningTree::generate_forked(start, &targets, &LightningConfig::default());
+
+        assert_eq!(tree.nodes[0].position, start);
+        assert_eq!(tree.strike_points.len(), targets.len());
+        for (&idx, &target) in tree.strike_points.iter().zip(&targets) {
+            assert_eq!(tree.nodes[idx].position, target, "Every target is hit");
+
+            // Each strike point is connected back to the root
+            let mut cursor = idx;
+            while let Some(parent) = tree.nodes[cursor].parent {
+                cursor = parent;
+            }
+            assert_eq!(cursor, 0);
+        }
+        assert_eq!(tree.main_path.last(), tree.strike_points.first());
+
+        let single =
+            LightningTree::generate_forked(start, &targets[..1], &LightningConfig::default());
+        let direct = LightningTree::generate(start, targets[0], &LightningConfig::default());
+        assert_eq!(single.get_line_positions(), direct.get_line_positions());
+    }
+
+    #[test]
+    fn test_chain_hop_selection() {
+        let mut world = World::new();
+        let near = world.spawn_empty().id();
+        let far = world.spawn_empty().id();
+        let out_of_range = world.spawn_empty().id();
+        let candidates = [
+            (far, Vec3::new(8.0, 0.0, 0.0)),
+            (near, Vec3::new(0.0, 0.0, 3.0)),
+            (out_of_range, Vec3::new(50.0, 0.0, 0.0)),
+        ];
+
+        let next = nearest_chain_target(Vec3::ZERO, 10.0, candidates.into_iter());
+        assert_eq!(next.map(|(entity, _)| entity), Some(near));
+
+        let remaining = candidates.into_iter().filter(|(entity, _)| *entity != near);
+        let next = nearest_chain_target(Vec3::ZERO, 10.0, remaining);
+        assert_eq!(next.map(|(entity, _)| entity), Some(far));
+
+        let next = nearest_chain_target(Vec3::ZERO, 1.0, candidates.into_iter());
+        assert!(next.is_none(), "Nothing should be in range");
+    }
+
+    #[test]
+    fn test_chain_end_keeps_host_entity() {
+        use bevy::ecs::system::RunSystemOnce;
+
+        let mut world = World::new();
+        world.init_resource::<Assets<EffectAsset>>();
+        world.init_resource::<Time>();
+        world.init_resource::<Messages<ChainLightningHit>>();
+
+        // The chain lives on the caster; with no candidates in range it ends at once
+        let caster = world.spawn(GlobalTransform::IDENTITY).id();
+        world.entity_mut(caster).insert(ChainLightning::<With<ChainLightningTarget>>::new(
+            caster,
+            10.0,
+            3,
+            0.1,
+            LightningConfig::default(),
+            Color::WHITE,
+        ));
+        world
+            .run_system_once(advance_chain_lightning::<With<ChainLightningTarget>>)
+            .unwrap();
+
+        let caster = world.entity(caster);
+        assert!(caster.contains::<GlobalTransform>());
+        assert!(!caster.contains::<ChainLightning>());
+    }
+
+    #[test]
+    fn test_leader_arrival_times() {
+        let end = Vec3::new(0.0, 0.0, 100.0);
+        let instant = LightningTree::generate(Vec3::ZERO, end, &LightningConfig::default());
+        assert!(instant.nodes.iter().all(|node| node.arrival_time == 0.0));
+        assert_eq!(instant.strike_time(), 0.0);
+
+        let config = LightningConfig {
+            growth: GrowthConfig {
+                leader_speed: 200.0,
+                ..default()
+            },
+            ..default()
+        };
+        let tree = LightningTree::generate(Vec3::ZERO, end, &config);
+        assert_eq!(
+            tree.get_line_positions(),
+            instant.get_line_positions(),
+            "Timing must not change the shape"
+        );
+        for node in &tree.nodes[1..] {
+            let parent = &tree.nodes[node.parent.unwrap()];
+            assert!(node.arrival_time > parent.arrival_time);
+        }
+        // Slowest possible main channel: the whole path at 70% speed plus a stall per node
+        let path_length: f32 = tree
+            .main_path
+            .windows(2)
+            .map(|pair| {
+                tree.nodes[pair[0]]
+                    .position
+                    .distance(tree.nodes[pair[1]].position)
+            })
+            .sum();
+        let strike_time = tree.strike_time();
+        assert!(strike_time >= path_length / (200.0 * 1.3));
+        assert!(strike_time <= path_length / (200.0 * 0.7) + 0.05 * tree.main_path.len() as f32);
+
+        let lightning = ProceduralLightning::from_tree(tree, 0.5, Color::WHITE);
+        assert!((lightning.lifetime.duration().as_secs_f32() - (strike_time + 0.5)).abs() < 1e-4);
+
+        // Stalls follow the distance travelled, so finer subdivision barely changes the
+        // timing (on a smooth bolt, whose path length converges as it is subdivided)
+        let growth = GrowthConfig {
+            leader_speed: 400.0,
+            ..default()
+        };
+        let strike_times: Vec<f32> = [6, 10, 12]
+            .into_iter()
+            .map(|max_depth| {
+                let config = LightningConfig {
+                    max_depth,
+                    hurst_exponent: 1.3,
+                    growth: growth.clone(),
+                    ..default()
+                };
+                LightningTree::generate(Vec3::ZERO, end, &config).strike_time()
+            })
+            .collect();
+        for strike_time in &strike_times[1..] {
+            assert!(
+                (strike_time / strike_times[0] - 1.0).abs() < 0.3,
+                "strike times {strike_times:?} should not grow with max_depth"
+            );
+        }
+    }
+
+    #[test]
+    fn test_stroke_schedule() {
+        let config = LightningConfig {
+            growth: GrowthConfig {
+                leader_speed: 400.0,
+                ..default()
+            },
+            ..default()
+        };
+        let tree = LightningTree::generate(Vec3::ZERO, Vec3::new(0.0, 0.0, 100.0), &config);
+        let strokes = StrokeConfig {
+            seed: 3,
+            restrike_count: 4,
+            ..default()
+        };
+        let schedule = strokes.schedule(&tree);
+        assert_eq!(schedule.len(), 5, "Return stroke plus four re-strikes");
+        assert_eq!(schedule[0].start, tree.strike_time());
+        assert!(schedule[0]
+            .offsets
+            .iter()
+            .all(|offset| *offset == Vec3::ZERO));
+        for pair in schedule.windows(2) {
+            assert!(
+                pair[1].start > pair[0].start + pair[0].duration,
+                "Strokes must not overlap"
+            );
+        }
+        for stroke in &schedule[1..] {
+            assert_eq!(stroke.offsets.len(), tree.main_path.len());
+            assert_eq!(stroke.offsets[0], Vec3::ZERO);
+            assert_eq!(*stroke.offsets.last().unwrap(), Vec3::ZERO);
+            assert!(stroke.brightness < schedule[0].brightness);
+        }
+        assert_eq!(
+            strokes.schedule(&tree)[2].offsets,
+            schedule[2].offsets,
+            "Same seed must give the same jitter"
+        );
+
+        let lightning =
+            ProceduralLightning::from_tree(tree, 0.05, Color::WHITE).with_strokes(&strokes);
+        let last = lightning.strokes.last().unwrap();
+        assert!(lightning.lifetime.duration().as_secs_f32() >= last.start + last.duration - 1e-4);
+    }
+
+    #[test]
+    fn test_continuous_arc_regeneration() {
+        let config = LightningConfig {
+            seed: 5,
+            ..default()
+        };
+        let (start, end) = (Vec3::ZERO, Vec3::new(0.0, 0.0, 50.0));
+        let mut lightning =
+            ProceduralLightning::continuous(start, end, config.clone(), 10.0, Color::WHITE);
+        let first = lightning.tree.get_line_positions();
+
+        let arc = lightning.continuous.as_mut().unwrap();
+        assert!((arc.period() - 0.1).abs() < 1e-6);
+        arc.regenerate(&mut lightning.tree);
+        assert_eq!(arc.generation, 1);
+        assert_ne!(
+            lightning.tree.get_line_positions(),
+            first,
+            "Each re-roll should differ"
+        );
+
+        let mut rng = ChaCha8Rng::seed_from_u64(6);
+        let expected = config.generate(start, end, &mut rng);
+        assert_eq!(
+            lightning.tree.get_line_positions(),
+            expected.get_line_positions()
+        );
+        assert_eq!(lightning.tree.main_path, expected.main_path);
+
+        arc.stop();
+        assert!(!arc.is_running());
+    }
+
+    #[test]
+    fn test_lightning_crawl() {
+        let mut tree = LightningTree::generate(
+            Vec3::ZERO,
+            Vec3::new(0.0, 0.0, 100.0),
+            &LightningConfig::default(),
+        );
+        let rest = tree.clone();
+        let mut crawl = LightningCrawl::new(0.2, 8.0);
+
+        let mut moved = false;
+        for _ in 0..10 {
+            crawl.apply(&mut tree, 0.016);
+            assert_eq!(tree.segments, rest.segments, "Topology must not change");
+            assert_eq!(tree.nodes[0].position, rest.nodes[0].position);
+            for &idx in &tree.strike_points {
+                assert_eq!(tree.nodes[idx].position, rest.nodes[idx].position);
+            }
+            for (node, original) in tree.nodes.iter().zip(&rest.nodes).skip(1) {
+                let segment = original
+                    .position
+                    .distance(rest.nodes[original.parent.unwrap()].position);
+                let offset = node.position.distance(original.position);
+                assert!(offset <= 0.2 * segment * 3f32.sqrt() + 1e-4);
+                moved |= offset > 0.0;
+            }
+        }
+        assert!(moved, "Inner nodes should shimmer");
+    }
+
+    #[test]
+    fn test_endpoint_tracking() {
+        use bevy::ecs::system::RunSystemOnce;
+
+        let mut world = World::new();
+        let turret = world.spawn(GlobalTransform::from_xyz(0.0, 0.0, 0.0)).id();
+        let enemy = world.spawn(GlobalTransform::from_xyz(0.0, 0.0, 100.0)).id();
+        let particles = world
+            .spawn((Transform::default(), EffectProperties::default()))
+            .id();
+        let mut lightning = ProceduralLightning::new(
+            Vec3::ZERO,
+            Vec3::new(0.0, 0.0, 100.0),
+            &LightningConfig::default(),
+            1.0,
+            Color::WHITE,
+        )
+        .with_source(turret, Vec3::ZERO)
+        .with_target(enemy, Vec3::new(0.0, 0.0, -10.0));
+        lightning.particle_entities.push(particles);
+        let bolt = world.spawn((lightning, Transform::default())).id();
+
+        // Enemy moves sideways and away
+        world
+            .entity_mut(enemy)
+            .insert(GlobalTransform::from_xyz(60.0, 0.0, 80.0));
+        world.run_system_once(track_lightning_endpoints).unwrap();
+
+        let tree = &world.get::<ProceduralLightning>(bolt).unwrap().tree;
+        let start = tree.nodes[0].position;
+        let end = tree.nodes[tree.strike_points[0]].position;
+        assert!(start.distance(Vec3::ZERO) < 1e-4);
+        assert!(end.distance(Vec3::new(60.0, 0.0, 70.0)) < 1e-3);
+
+        // Sideways offsets from the axis keep their size
+        let original = LightningTree::generate(
+            Vec3::ZERO,
+            Vec3::new(0.0, 0.0, 100.0),
+            &LightningConfig::default(),
+        );
+        let axis = (end - start).normalize();
+        for (node, before) in tree.nodes.iter().zip(&original.nodes) {
+            let lateral = (node.position - start)
+                .reject_from_normalized(axis)
+                .length();
+            let lateral_before = Vec2::new(before.position.x, before.position.y).length();
+            assert!((lateral - lateral_before).abs() < 1e-3);
+        }
+
+        // Particles follow the turret and stream toward the moved target
+        world
+            .entity_mut(turret)
+            .insert(GlobalTransform::from_xyz(10.0, 0.0, 0.0));
+        world.run_system_once(track_lightning_endpoints).unwrap();
+        let emitter = world.get::<Transform>(particles).unwrap().translation;
+        assert!(emitter.distance(Vec3::new(10.0, 0.0, 0.0)) < 1e-4);
+        let direction = world
+            .get::<EffectProperties>(particles)
+            .unwrap()
+            .get_stored(PARTICLE_DIRECTION_PROPERTY);
+        let expected = Vec3::new(50.0, 0.0, 70.0).normalize();
+        assert!(matches!(
+            direction,
+            Some(Value::Vector(vector)) if vector.as_vec3().distance(expected) < 1e-4
+        ));
+    }
+
+    #[test]
+    fn test_tracking_uses_current_frame() {
+        let mut app = App::new();
+        app.add_plugins((
+            MinimalPlugins,
+            TransformPlugin,
+            AssetPlugin::default(),
+            bevy::gizmos::GizmoPlugin,
+            ProceduralLightningPlugin,
+        ));
+        let turret = app.world_mut().spawn(Transform::default()).id();
+        let enemy = app
+            .world_mut()
+            .spawn(Transform::from_xyz(0.0, 0.0, 100.0))
+            .id();
+        let lightning = ProceduralLightning::new(
+            Vec3::ZERO,
+            Vec3::new(0.0, 0.0, 100.0),
+            &LightningConfig::default(),
+            10.0,
+            Color::WHITE,
+        )
+        .with_source(turret, Vec3::ZERO)
+        .with_target(enemy, Vec3::ZERO);
+        let bolt = app
+            .world_mut()
+            .spawn((lightning, Transform::default()))
+            .id();
+        app.update();
+
+        // Gameplay moves the turret's local transform in `Update`
+        app.add_systems(Update, move |mut transforms: Query<&mut Transform>| {
+            transforms.get_mut(turret).unwrap().translation = Vec3::new(20.0, 0.0, 0.0);
+        });
+        app.update();
+        let tree = &app.world().get::<ProceduralLightning>(bolt).unwrap().tree;
+        assert!(tree.nodes[0].position.distance(Vec3::new(20.0, 0.0, 0.0)) < 1e-4);
+    }
+
+    #[test]
+    fn test_retarget() {
+        let cached = LightningTree::generate(Vec3::ZERO, Vec3::Z, &LightningConfig::default());
+        let (start, end) = (Vec3::new(10.0, 50.0, 0.0), Vec3::new(10.0, 0.0, 0.0));
+
+        let tree = cached.retargeted(start, end);
+        assert_eq!(tree.segments, cached.segments);
+        assert!(tree.nodes[0].position.distance(start) < 1e-4);
+        assert!(tree.nodes[tree.strike_points[0]].position.distance(end) < 1e-3);
+        assert_eq!(tree.root, tree.nodes[0].position);
+
+        // Proportional: the whole shape scales by 50, so every distance does too
+        for &(a, b) in &tree.segments {
+            let before = cached.nodes[a].position.distance(cached.nodes[b].position);
+            let after = tree.nodes[a].position.distance(tree.nodes[b].position);
+            assert!((after - before * 50.0).abs() < 1e-2);
+        }
+
+        // Stretch: sideways offsets keep their size
+        let mut stretched = cached.clone();
+        stretched.stretch_to(start, end);
+        let axis = (end - start).normalize();
+        for (node, before) in stretched.nodes.iter().zip(&cached.nodes) {
+            let lateral = (node.position - start)
+                .reject_from_normalized(axis)
+                .length();
+            let lateral_before = Vec2::new(before.position.x, before.position.y).length();
+            assert!((lateral - lateral_before).abs() < 1e-4);
+        }
+    }
+
+    #[test]
+    fn test_lightning_cache() {
+        let dense = LightningConfig {
+            alpha: 0.3,
+            beta: 0.3,
+            gamma: 0.5,
+            max_depth: 10,
+            max_branch_depth: 4,
+            ..default()
+        };
+        assert_eq!(dense, dense.clone());
+        assert_ne!(dense, LightningConfig::default());
+        let mut hashes = std::collections::HashSet::new();
+        hashes.insert(dense.clone());
+        assert!(hashes.contains(&dense.clone()));
+
+        let mut cache = LightningCache::new(4);
+        assert_eq!(cache.variants(&dense).len(), 4);
+        let unit = cache.variants(&dense)[1].clone();
+        let seeded = LightningConfig {
+            seed: 1,
+            ..dense.clone()
+        };
+        assert_eq!(
+            unit.get_line_positions(),
+            LightningTree::generate(Vec3::ZERO, Vec3::Z, &seeded).get_line_positions()
+        );
+
+        let (start, end) = (Vec3::new(0.0, 100.0, 0.0), Vec3::new(20.0, 0.0, 5.0));
+        let tree = cache.tree(&dense, 5, start, end);
+        assert_eq!(
+            tree.segments, unit.segments,
+            "Index should wrap to variant 1"
+        );
+        assert!(tree.nodes[0].position.distance(start) < 1e-4);
+        assert!(tree.nodes[tree.strike_points[0]].position.distance(end) < 1e-3);
+
+        // Growth timing is recomputed for the retargeted length
+        let growing = LightningConfig {
+            growth: GrowthConfig {
+                leader_speed: 200.0,
+                ..default()
+            },
+            ..dense
+        };
+        let end = Vec3::new(0.0, 0.0, 100.0);
+        let cached = cache.tree(&growing, 0, Vec3::ZERO, end);
+        let direct = LightningTree::generate(Vec3::ZERO, end, &growing);
+        assert!(cached.strike_time() > 0.0);
+        assert!((cached.strike_time() - direct.strike_time()).abs() < 1e-3);
 
-        // Draw lightning using gizmos
-        // With flicker: alternates on/off based on timer progress
-        // Without flicker: draw every frame
-        let should_draw = if lightning.enable_flicker {
-            // Flicker on/off - visible for first half of timer cycle
-            lightning.animation_timer.elapsed_secs() / lightning.animation_timer.duration().as_secs_f32() < 0.5
-        } else {
-            true
+        // Growth and pruning are applied after retargeting, so they share the variants
+        let pruned = LightningConfig {
+            prune: PruneConfig {
+                min_length: 5.0,
+                ..default()
+            },
+            ..growing
         };
-        
-        if should_draw {
-            for (start_idx, end_idx) in &lightning.tree.segments {
-                let start = transform.transform_point(lightning.tree.nodes[*start_idx].position);
-                let end = transform.transform_point(lightning.tree.nodes[*end_idx].position);
-                let energy = lightning.tree.nodes[*start_idx].energy;
+        cache.tree(&pruned, 0, Vec3::ZERO, end);
+        assert_eq!(cache.entries.len(), 1);
 
-                // Vary color intensity by energy
-                let alpha = if lightning.show_gizmos { 0.9 } else { 0.7 };
-                let color = lightning.color.with_alpha(energy * alpha);
-                gizmos.line(start, end, color);
-            }
+        // Side-view bolts stay in the screen plane after retargeting
+        let side_view = LightningConfig {
+            displacement_plane: DisplacementPlane::XY,
+            ..default()
+        };
+        let end = Vec3::new(100.0, 0.0, 0.0);
+        let cached = cache.tree(&side_view, 0, Vec3::ZERO, end);
+        let direct = LightningTree::generate(Vec3::ZERO, end, &side_view);
+        assert!(cached.nodes.iter().all(|node| node.position.z.abs() < 1e-3));
+        assert_eq!(cached.segments, direct.segments);
+        for (cached, direct) in cached.nodes.iter().zip(&direct.nodes) {
+            assert!(cached.position.distance(direct.position) < 1e-3);
         }
     }
-}
 
-/// Cleanup expired lightning effects
-#[allow(clippy::needless_pass_by_value)]
-fn cleanup_expired_lightning(mut commands: Commands, query: Query<(Entity, &ProceduralLightning)>) {
-    for (entity, lightning) in &query {
-        if lightning.lifetime.is_finished() {
-            // Despawn all particle entities first
-            for &particle_entity in &lightning.particle_entities {
-                if let Ok(mut entity_commands) = commands.get_entity(particle_entity) {
-                    entity_commands.despawn();
-                }
-            }
-            // Then despawn the main lightning entity
-            commands.entity(entity).despawn();
+    #[test]
+    fn test_topology_stable_lerp() {
+        let config = LightningConfig {
+            seed: 9,
+            displacement_mode: DisplacementMode::Volumetric,
+            ..default()
+        };
+        let (start, end) = (Vec3::ZERO, Vec3::new(0.0, 0.0, 100.0));
+        let [a, b] = LightningTree::generate_pair(start, end, &config, [1, 2]);
+        assert_eq!(a.segments, b.segments, "Pairs must share their topology");
+        assert_eq!(a.main_path, b.main_path);
+        assert_ne!(a.get_line_positions(), b.get_line_positions());
+
+        assert_eq!(
+            a.lerp(&b, 0.0).unwrap().get_line_positions(),
+            a.get_line_positions()
+        );
+        assert_eq!(
+            b.lerp(&a, 0.0).unwrap().get_line_positions(),
+            b.get_line_positions()
+        );
+        let half = a.lerp(&b, 0.5).unwrap();
+        for ((node, x), y) in half.nodes.iter().zip(&a.nodes).zip(&b.nodes) {
+            assert!(node.position.distance((x.position + y.position) * 0.5) < 1e-4);
         }
-    }
-}
 
-/// Helper function to spawn a procedural lightning effect with particles
-pub fn spawn_procedural_lightning(
-    commands: &mut Commands,
-    effects: &mut ResMut<Assets<EffectAsset>>,
-    start: Vec3,
-    end: Vec3,
-    config: &LightningConfig,
-    lifetime_secs: f32,
-    color: Color,
-    show_gizmos: bool,
-    show_particles: bool,
-) -> Entity {
-    let mut lightning = ProceduralLightning::new(start, end, config, lifetime_secs, color);
-    lightning.show_gizmos = show_gizmos;
+        let other = LightningTree::generate(start, end, &LightningConfig::default());
+        assert!(a.lerp(&other, 0.5).is_none());
+    }
 
-    // Create multi-layered particle effects if enabled
-    let particle_entities = if show_particles {
-        create_procedural_lightning_particle_effects(commands, effects, &lightning.tree, color)
-    } else {
-        Vec::new()
-    };
-    lightning.particle_entities = particle_entities;
+    #[test]
+    fn test_hierarchical_seeding() {
+        let (start, end) = (Vec3::ZERO, Vec3::new(0.0, 0.0, 100.0));
+        let config = LightningConfig {
+            seed: 4,
+            max_depth: 6,
+            seed_mode: SeedMode::Hierarchical,
+            ..default()
+        };
+        let coarse = LightningTree::generate(start, end, &config);
 
-    commands.spawn((lightning, Transform::default())).id()
-}
+        // Deeper subdivision only adds detail: every coarse node is still there
+        let fine = LightningTree::generate(
+            start,
+            end,
+            &LightningConfig {
+                max_depth: 7,
+                ..config.clone()
+            },
+        );
+        assert!(fine.nodes.len() > coarse.nodes.len());
+        for node in &coarse.nodes {
+            assert!(fine
+                .nodes
+                .iter()
+                .any(|other| other.position == node.position));
+        }
 
-/// Create traveling ionized particle effect for procedural lightning
-///
-/// Returns vector of entity IDs for particle effects
-///
-/// Creates particles that travel from spawn point to target with random scatter
-fn create_procedural_lightning_particle_effects(
-    commands: &mut Commands,
-    effects: &mut ResMut<Assets<EffectAsset>>,
-    tree: &LightningTree,
-    color: Color,
-) -> Vec<Entity> {
-    let mut particle_entities = Vec::new();
+        // Branching changes leave the main channel alone
+        let bushier = LightningTree::generate(
+            start,
+            end,
+            &LightningConfig {
+                gamma: 0.6,
+                ..config.clone()
+            },
+        );
+        let main_channel = |tree: &LightningTree| -> Vec<Vec3> {
+            tree.main_path
+                .iter()
+                .map(|&idx| tree.nodes[idx].position)
+                .collect()
+        };
+        assert_eq!(main_channel(&bushier), main_channel(&coarse));
+    }
 
-    // Extract color components
-    let [r, g, b, _] = color.to_srgba().to_f32_array();
-    let base_color = Vec4::new(r, g, b, 1.0);
+    #[test]
+    fn test_detail_levels() {
+        let (start, end) = (Vec3::ZERO, Vec3::new(0.0, 0.0, 100.0));
+        let config = LightningConfig {
+            seed: 2,
+            max_depth: 7,
+            seed_mode: SeedMode::Hierarchical,
+            ..default()
+        };
+        let tree = LightningTree::generate(start, end, &config);
+        assert_eq!(tree.max_level(), 7);
+        assert_eq!(
+            tree.detail_segments(7).collect::<Vec<_>>(),
+            tree.segments,
+            "The finest level is the full tree"
+        );
 
-    let start_pos = tree.nodes[0].position;
-    let end_pos = tree.nodes.last().map(|n| n.position).unwrap_or(start_pos);
-    
-    // Calculate direction and distance for traveling particles
-    let direction = (end_pos - start_pos).normalize_or_zero();
-    let distance = start_pos.distance(end_pos);
-    
-    // Calculate appropriate lifetime based on distance (particles should reach target)
-    let base_speed = 20.0; // units per second
-    let particle_lifetime = (distance / base_speed).max(0.3);
+        let coarse = tree.at_detail_level(4);
+        let direct = LightningTree::generate(
+            start,
+            end,
+            &LightningConfig {
+                max_depth: 4,
+                ..config.clone()
+            },
+        );
+        assert_eq!(coarse.segments.len(), direct.segments.len());
+        assert_eq!(coarse.nodes.len(), direct.nodes.len());
+        assert_eq!(coarse.nodes[coarse.strike_points[0]].position, end);
+        for node in &coarse.nodes {
+            assert!(direct
+                .nodes
+                .iter()
+                .any(|other| other.position == node.position));
+        }
 
-    // ==== Traveling Ionized Particles ====
-    // Creates particles that travel from spawn to target with random scatter
-    let writer = ExprWriter::new();
-    
-    let intensity = 12.0;
-    let lifetime = writer.lit(particle_lifetime * 0.8).uniform(writer.lit(particle_lifetime * 1.2)).expr();
-    let age = writer.lit(0.0).expr();
-    
-    // Spawn at center with small radius for scatter
-    let spawn_center = writer.lit(Vec3::ZERO).expr();
-    let spawn_radius = writer.lit(0.5).expr();
-    
-    // Velocity pointing from start to end with random variance
-    let base_velocity = direction * base_speed;
-    let velocity_vec = writer.lit(base_velocity * 0.8).uniform(writer.lit(base_velocity * 1.2)).expr();
-    
-    let drag = writer.lit(1.5).expr();
+        let lod = LightningLod {
+            full_detail_distance: 50.0,
+            min_level: 2,
+        };
+        assert_eq!(lod.level_for_distance(10.0, 7), 7);
+        assert_eq!(lod.level_for_distance(100.0, 7), 6);
+        assert_eq!(lod.level_for_distance(350.0, 7), 4);
+        assert_eq!(lod.level_for_distance(1.0e6, 7), 2);
+    }
 
-    let traveling_effect = EffectAsset::new(
-        512, 
-        SpawnerSettings::rate(200.0.into()), 
-        writer.finish()
-    )
-    .with_name("ionized_particles")
-    .init(SetAttributeModifier::new(Attribute::LIFETIME, lifetime))
-    .init(SetAttributeModifier::new(Attribute::AGE, age))
-    .init(SetPositionSphereModifier {
-        center: spawn_center,
-        radius: spawn_radius,
-        dimension: ShapeDimension::Volume,
-    })
-    .init(SetAttributeModifier::new(Attribute::VELOCITY, velocity_vec))
-    .update(LinearDragModifier::new(drag))
-    .render(ColorOverLifetimeModifier::new({
-        let mut gradient = bevy_hanabi::Gradient::new();
-        gradient.add_key(0.0, base_color * intensity);
-        gradient.add_key(0.4, base_color * intensity * 0.8);
-        gradient.add_key(0.8, base_color * intensity * 0.3);
-        gradient.add_key(1.0, Vec4::ZERO);
-        gradient
-    }))
-    .render(SizeOverLifetimeModifier {
-        gradient: {
-            let mut gradient = bevy_hanabi::Gradient::new();
-            gradient.add_key(0.0, Vec3::splat(0.4));
-            gradient.add_key(0.2, Vec3::splat(0.6));
-            gradient.add_key(0.7, Vec3::splat(0.3));
-            gradient.add_key(1.0, Vec3::splat(0.1));
-            gradient
-        },
-        screen_space_size: false,
-    });
+    #[test]
+    fn test_roughness_and_distributions() {
+        let (start, end) = (Vec3::ZERO, Vec3::new(0.0, 0.0, 100.0));
+        let path_length = |config: &LightningConfig| -> f32 {
+            let tree = LightningTree::generate(start, end, config);
+            tree.main_path
+                .windows(2)
+                .map(|pair| {
+                    tree.nodes[pair[0]]
+                        .position
+                        .distance(tree.nodes[pair[1]].position)
+                })
+                .sum()
+        };
+        let with_hurst = |hurst_exponent| LightningConfig {
+            seed: 8,
+            hurst_exponent,
+            ..default()
+        };
+        let smooth = path_length(&with_hurst(1.5));
+        let regular = path_length(&with_hurst(1.0));
+        let crinkly = path_length(&with_hurst(0.3));
+        assert!(
+            smooth < regular && regular < crinkly,
+            "{smooth} {regular} {crinkly}"
+        );
 
-    let traveling_handle = effects.add(traveling_effect);
-    let traveling_entity = commands
-        .spawn((
-            ParticleEffect::new(traveling_handle),
-            Transform::from_translation(start_pos),
-        ))
-        .id();
-    particle_entities.push(traveling_entity);
+        // Offsets shrink with the level even when rough, so the bolt stays near its axis
+        for hurst_exponent in [0.3, 0.5, 0.7, 1.0] {
+            for max_depth in 8..=10 {
+                let config = LightningConfig {
+                    max_depth,
+                    ..with_hurst(hurst_exponent)
+                };
+                let tree = LightningTree::generate(start, end, &config);
+                let spread = tree
+                    .nodes
+                    .iter()
+                    .map(|node| node.position.truncate().length())
+                    .fold(0.0, f32::max);
+                assert!(
+                    spread < 100.0,
+                    "H = {hurst_exponent}, depth {max_depth}: spread {spread}"
+                );
+            }
+        }
 
-    particle_entities
-}
+        for distribution in [
+            DisplacementDistribution::Gaussian,
+            DisplacementDistribution::Cauchy,
+        ] {
+            let config = LightningConfig {
+                displacement_distribution: distribution,
+                ..default()
+            };
+            let tree = LightningTree::generate(start, end, &config);
+            assert_eq!(tree.nodes[tree.strike_points[0]].position, end);
+            assert!(tree
+                .nodes
+                .iter()
+                .all(|node| node.position.is_finite() && node.position.length() < 1000.0));
+        }
 
-#[cfg(test)]
-mod tests {
-    use super::*;
+        let mut rng = ChaCha8Rng::seed_from_u64(1);
+        let samples: Vec<f32> = (0..4000)
+            .map(|_| sample_displacement(&mut rng, DisplacementDistribution::Gaussian))
+            .collect();
+        let mean = samples.iter().sum::<f32>() / samples.len() as f32;
+        let variance =
+            samples.iter().map(|x| (x - mean).powi(2)).sum::<f32>() / samples.len() as f32;
+        assert!(mean.abs() < 0.05);
+        assert!(
+            (variance - 1.0 / 3.0).abs() < 0.05,
+            "Should match the uniform spread"
+        );
+    }
 
     #[test]
-    fn test_lightning_generation() {
-        let config = LightningConfig::default();
-        let start = Vec3::new(0.0, 0.0, 0.0);
-        let end = Vec3::new(0.0, 0.0, 100.0);
+    fn test_default_matches_original_algorithm() {
+        // The midpoint subdivision the crate started with, before any of the config options
+        fn original(start: Vec3, end: Vec3, config: &LightningConfig) -> (Vec<Vec3>, usize) {
+            let mut rng = ChaCha8Rng::seed_from_u64(config.seed);
+            let mut positions = vec![start, end];
+            let mut segment_count = 0;
+            let mut to_subdivide = vec![(0, 1, 0, 0)];
+            while let Some((start_idx, end_idx, depth, branch_depth)) = to_subdivide.pop() {
+                if depth >= config.max_depth {
+                    segment_count += 1;
+                    continue;
+                }
+                let (start_pos, end_pos): (Vec3, Vec3) = (positions[start_idx], positions[end_idx]);
+                let segment = end_pos - start_pos;
+                let length = segment.length();
+                let perpendicular = Vec3::new(-segment.z, 0.0, segment.x).normalize();
+                let displacement: f32 = rng.gen_range(-1.0..1.0);
+                let midpoint = (start_pos + end_pos) * 0.5
+                    + perpendicular * displacement * config.beta * length;
+                let mid_idx = positions.len();
+                positions.push(midpoint);
+                to_subdivide.push((start_idx, mid_idx, depth + 1, branch_depth));
+                to_subdivide.push((mid_idx, end_idx, depth + 1, branch_depth));
 
-        let tree = LightningTree::generate(start, end, &config);
+                if branch_depth < config.max_branch_depth {
+                    let branch_prob = config.gamma
+                        * f32::exp(-config.alpha * (depth as f32 + branch_depth as f32));
+                    if rng.gen::<f32>() < branch_prob {
+                        let branch_length =
+                            length * 0.5 * f32::exp(-config.alpha * branch_depth as f32);
+                        let to_target = (end - midpoint).normalize();
+                        let perp_component = perpendicular * rng.gen_range(-0.6..0.6);
+                        let forward_component = to_target * rng.gen_range(0.3..0.8);
+                        let branch_dir = (perp_component + forward_component).normalize();
+                        positions.push(midpoint + branch_dir * branch_length);
+                        to_subdivide.push((
+                            mid_idx,
+                            positions.len() - 1,
+                            depth + 1,
+                            branch_depth + 1,
+                        ));
+                    }
+                }
+            }
+            (positions, segment_count)
+        }
 
-        assert!(!tree.nodes.is_empty(), "Should generate nodes");
-        assert!(!tree.segments.is_empty(), "Should generate segments");
-        assert_eq!(tree.nodes[0].position, start, "Start node should match");
+        let (start, end) = (Vec3::ZERO, Vec3::new(30.0, 0.0, 100.0));
+        for seed in 0..8 {
+            let config = LightningConfig { seed, ..default() };
+            let tree = LightningTree::generate(start, end, &config);
+            let (positions, segment_count) = original(start, end, &config);
+            // Same shape up to float rounding in the perpendicular
+            assert_eq!(tree.segments.len(), segment_count, "seed {seed}");
+            assert_eq!(tree.nodes.len(), positions.len(), "seed {seed}");
+            for (node, &position) in tree.nodes.iter().zip(&positions) {
+                assert!(
+                    node.position.distance(position) < 1e-3,
+                    "Default bolts must not change (seed {seed})"
+                );
+            }
+        }
     }
 
     #[test]
-    fn test_deterministic_generation() {
+    fn test_noise_displacement() {
         let config = LightningConfig {
-            seed: 42,
+            noise: Some(NoiseDisplacement::default()),
             ..default()
         };
-        let start = Vec3::ZERO;
-        let end = Vec3::new(0.0, 0.0, 100.0);
+        let main_channel = |tree: &LightningTree| -> Vec<Vec3> {
+            tree.main_path
+                .iter()
+                .map(|&idx| tree.nodes[idx].position)
+                .collect()
+        };
+        let (start, end) = (Vec3::ZERO, Vec3::new(0.0, 0.0, 100.0));
+        let bolt = LightningTree::generate(start, end, &config);
+        assert!(main_channel(&bolt)
+            .iter()
+            .any(|position| position.x.abs() > 1.0));
 
-        let tree1 = LightningTree::generate(start, end, &config);
-        let tree2 = LightningTree::generate(start, end, &config);
+        // The field, not the seed, shapes the main channel
+        let reseeded = LightningTree::generate(
+            start,
+            end,
+            &LightningConfig {
+                seed: 99,
+                ..config.clone()
+            },
+        );
+        assert_eq!(main_channel(&reseeded), main_channel(&bolt));
 
-        assert_eq!(
-            tree1.nodes.len(),
-            tree2.nodes.len(),
-            "Same seed should produce same result"
+        // A neighbouring bolt bends the same way
+        let shift = Vec3::new(0.0, 0.0, 0.5);
+        let neighbour = LightningTree::generate(start + shift, end + shift, &config);
+        for (a, b) in main_channel(&bolt).iter().zip(main_channel(&neighbour)) {
+            assert!((a.x - b.x).abs() < 2.0, "{a} vs {b}");
+        }
+
+        // Cached variants each get their own part of the field
+        let unit_noise = LightningConfig {
+            noise: Some(NoiseDisplacement {
+                frequency: 4.0,
+                ..default()
+            }),
+            ..default()
+        };
+        let mut cache = LightningCache::new(3);
+        let variants = cache.variants(&unit_noise);
+        assert_ne!(main_channel(&variants[0]), main_channel(&variants[1]));
+        assert_ne!(main_channel(&variants[1]), main_channel(&variants[2]));
+    }
+
+    #[test]
+    fn test_energy_models() {
+        let (start, end) = (Vec3::ZERO, Vec3::new(0.0, 0.0, 100.0));
+        for model in [EnergyModel::DownstreamTips, EnergyModel::PathDecay] {
+            let config = LightningConfig {
+                gamma: 0.6,
+                energy: EnergyConfig { model, ..default() },
+                ..default()
+            };
+            let tree = LightningTree::generate(start, end, &config);
+            assert!(tree.branch_count() > 1);
+            assert_eq!(tree.nodes[0].energy, 1.0);
+            for node in &tree.nodes {
+                assert!((0.0..=1.0).contains(&node.energy), "{model:?}");
+                // Energy never increases away from the root
+                if let Some(parent) = node.parent {
+                    assert!(node.energy <= tree.nodes[parent].energy + 1e-6, "{model:?}");
+                }
+                // Where a branch forks off, the main channel carries more than the branch
+                if node.branch_id == 0 {
+                    for &child in node.children.iter().skip(1) {
+                        assert!(tree.nodes[child].energy < tree.nodes[node.children[0]].energy);
+                    }
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn test_generation_budget() {
+        let (start, end) = (Vec3::ZERO, Vec3::new(0.0, 0.0, 100.0));
+        let heavy = LightningConfig {
+            max_depth: 15,
+            max_branch_depth: 6,
+            gamma: 0.6,
+            ..default()
+        };
+        let capped = LightningConfig {
+            max_nodes: Some(2000),
+            max_segments: Some(1500),
+            ..heavy.clone()
+        };
+        let tree = LightningTree::generate(start, end, &capped);
+        assert!(tree.truncated);
+        assert!(tree.nodes.len() <= 2000);
+        assert!(tree.segments.len() <= 1500);
+        assert_eq!(tree.nodes[tree.strike_points[0]].position, end);
+
+        // The main channel gets its detail before the branches
+        let main_segments = tree.main_path.len() - 1;
+        let branch_segments = tree.segments.len() - main_segments;
+        assert!(
+            main_segments > branch_segments,
+            "{main_segments} vs {branch_segments}"
         );
-        assert_eq!(tree1.segments.len(), tree2.segments.len());
+
+        // A budget that is never reached doesn't change the bolt
+        for seed in 0..4 {
+            let uncapped = LightningConfig { seed, ..default() };
+            let roomy = LightningConfig {
+                max_nodes: Some(1_000_000),
+                ..uncapped.clone()
+            };
+            let tree = LightningTree::generate(start, end, &roomy);
+            let reference = LightningTree::generate(start, end, &uncapped);
+            assert!(!tree.truncated);
+            assert!(!reference.truncated);
+            assert_eq!(tree.segments, reference.segments);
+            assert_eq!(tree.get_line_positions(), reference.get_line_positions());
+        }
+
+        // Caps below the starting nodes keep the bare root -> end segment
+        let starved = LightningConfig {
+            max_nodes: Some(1),
+            ..default()
+        };
+        let tree = LightningTree::generate(start, end, &starved);
+        assert!(tree.truncated);
+        assert_eq!(tree.nodes.len(), 2);
+        assert_eq!(tree.segments, vec![(0, 1)]);
+    }
+
+    #[test]
+    fn test_branch_pruning() {
+        let (start, end) = (Vec3::ZERO, Vec3::new(0.0, 0.0, 100.0));
+        let bushy = LightningConfig {
+            seed: 6,
+            gamma: 0.8,
+            ..default()
+        };
+        let full = LightningTree::generate(start, end, &bushy);
+        let pruned = LightningTree::generate(
+            start,
+            end,
+            &LightningConfig {
+                prune: PruneConfig {
+                    min_length: 10.0,
+                    min_tip_energy: 0.1,
+                },
+                ..bushy.clone()
+            },
+        );
+        assert!(pruned.nodes.len() < full.nodes.len());
+        assert!(pruned.branch_count() < full.branch_count());
+        assert_eq!(pruned.main_path.len(), full.main_path.len());
+        assert_eq!(pruned.nodes[pruned.strike_points[0]].position, end);
+
+        // Indices stay consistent after compaction
+        assert_eq!(pruned.segments.len(), pruned.nodes.len() - 1);
+        for &(parent, child) in &pruned.segments {
+            assert!(parent < pruned.nodes.len() && child < pruned.nodes.len());
+            assert_eq!(pruned.nodes[child].parent, Some(parent));
+        }
+        for branch_id in 1..pruned.branch_count() {
+            let branch = pruned.branch_nodes(branch_id);
+            let tip = &pruned.nodes[*branch.last().unwrap()];
+            assert!(tip.energy >= 0.1);
+        }
+
+        // Forked channels lead to strike points and are never pruned
+        let targets = [Vec3::new(-40.0, 0.0, 100.0), Vec3::new(40.0, 0.0, 100.0)];
+        let mut forked = LightningTree::generate_forked(start, &targets, &bushy);
+        forked.prune(1.0e6, 1.0);
+        assert_eq!(forked.tips().count(), 2);
+        for (&idx, target) in forked.strike_points.iter().zip(targets) {
+            assert_eq!(forked.nodes[idx].position, target);
+        }
+
+        // Cached variants are pruned at world scale, like a direct bolt
+        let short_stubs = LightningConfig {
+            prune: PruneConfig {
+                min_length: 2.0,
+                ..default()
+            },
+            ..bushy.clone()
+        };
+        let direct = LightningTree::generate(start, end, &short_stubs);
+        let cached = LightningCache::new(1).tree(&short_stubs, 0, start, end);
+        assert!(direct.branch_count() > 1);
+        assert_eq!(cached.branch_count(), direct.branch_count());
+        assert_eq!(cached.segments, direct.segments);
     }
 
     #[test]